@@ -0,0 +1,24 @@
+//! Produce a shareable test fixture from a real bill file.
+//! Usage: cargo run --example anonymize -- <input bill file> <output fixture>
+use std::fs;
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckanon::DuckAnonymizer;
+use duckbill::duckfile::duckdata::DuckData;
+use duckbill::duckfile::duckerror::DuckError;
+
+fn main() -> Result<(), DuckError> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: anonymize <input bill file> <output fixture>");
+        return Err(DuckError::OpCancelled);
+    }
+
+    let original = DuckFile::try_from(DuckData::from(fs::read(&args[1])?))?;
+    let anon = DuckAnonymizer::new().anonymize_file(&original)?;
+    println!("Anonymized {} bills", anon.get_bill_count());
+
+    let anon_data: DuckData = anon.into();
+    fs::write(&args[2], anon_data.as_ref())?;
+    println!("Fixture written to {}", args[2]);
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! Account IDs, both raw bytes and loosely typed operator input
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use duckbill::duckfile::duckacctid::DuckAcctId;
+use duckbill::duckfile::duckdata::DuckData;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DuckAcctId::validate(&DuckData::from(data));
+    let _ = DuckAcctId::try_from(data.to_vec());
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = DuckAcctId::normalize(text);
+    }
+});
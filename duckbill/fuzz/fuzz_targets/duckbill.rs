@@ -0,0 +1,17 @@
+//! Single bill records: parsing must return an error, never panic
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckdata::DuckData;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(bill) = DuckBill::new(DuckData::from(data)) {
+        let _ = bill.get_name();
+        let _ = bill.get_service_address();
+        let _ = bill.get_mailing_address();
+        let _ = bill.get_amount_due();
+        let _ = bill.get_meter_readings();
+        let _ = bill.to_string();
+    }
+});
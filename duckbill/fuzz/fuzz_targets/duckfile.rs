@@ -0,0 +1,20 @@
+//! Whole bill files: parsing must return an error, never panic
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckdata::DuckData;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(quack) = DuckFile::try_from(DuckData::from(data)) {
+        for bill in quack[..].iter() {
+            let _ = bill.get_name();
+            let _ = bill.get_service_address();
+            let _ = bill.get_mailing_address();
+            let _ = bill.get_amount_due();
+            let _ = bill.get_meter_readings();
+        }
+        let _ = quack.suggest_accounts("52-1111111-1", 5);
+        let _ = quack.search_text("MAIN ST");
+    }
+});
@@ -0,0 +1,62 @@
+"""Read, pick from and write bill run files."""
+from os import PathLike
+from typing import Iterable, Iterator, List, Optional, Union, overload
+
+class DuckbillError(Exception): ...
+class BillFileError(DuckbillError): ...
+class AccountIdError(DuckbillError): ...
+
+class AccountId:
+    def __init__(self, text: str) -> None: ...
+    @property
+    def digits(self) -> str: ...
+    def __hash__(self) -> int: ...
+
+class MeterReading:
+    meter_id: str
+    previous_date: str
+    previous_reading: int
+    present_date: str
+    present_reading: int
+    code: str
+    usage: int
+    water: Optional[int]
+    sewer: Optional[int]
+    total: int
+
+class Bill:
+    @property
+    def account_id(self) -> AccountId: ...
+    @property
+    def bill_number(self) -> int: ...
+    @property
+    def name(self) -> Optional[str]: ...
+    @property
+    def service_address(self) -> List[str]: ...
+    @property
+    def mailing_address(self) -> List[str]: ...
+    @property
+    def amount_due(self) -> Optional[int]: ...
+    @property
+    def meter_readings(self) -> List[MeterReading]: ...
+    @property
+    def plain_text(self) -> List[str]: ...
+    def to_bytes(self) -> bytes: ...
+
+class DuckFile:
+    def __init__(self, bills: Optional[Iterable[Bill]] = None) -> None: ...
+    @staticmethod
+    def open(path: Union[str, PathLike]) -> DuckFile: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> DuckFile: ...
+    def __len__(self) -> int: ...
+    @overload
+    def __getitem__(self, index: int) -> Bill: ...
+    @overload
+    def __getitem__(self, index: slice) -> List[Bill]: ...
+    def __iter__(self) -> Iterator[Bill]: ...
+    def index_of(self, account: Union[AccountId, str]) -> Optional[int]: ...
+    def index_of_bill_number(self, bill_number: int) -> Optional[int]: ...
+    def select(self, indexes: Iterable[int]) -> DuckFile: ...
+    def to_bytes(self, compression: Optional[str] = None, entry_name: str = "bills") -> bytes: ...
+    def write(self, path: Union[str, PathLike], compression: Optional[str] = None) -> None: ...
@@ -0,0 +1,16 @@
+#!/bin/sh
+# Build the bindings into a virtual environment with maturin and run the Python tests.
+# Set PYTHON to test with another interpreter; the environment is kept in target/venv.
+set -e
+cd "$(dirname "$0")"
+
+venv="$(pwd)/target/venv"
+if [ ! -x "$venv/bin/python" ]; then
+    "${PYTHON:-python3}" -m venv "$venv"
+fi
+if [ ! -x "$venv/bin/maturin" ]; then
+    "$venv/bin/python" -m pip install --quiet maturin
+fi
+
+VIRTUAL_ENV="$venv" "$venv/bin/maturin" develop --quiet
+"$venv/bin/python" -m unittest discover -s tests -v
@@ -0,0 +1,377 @@
+//! Python bindings for duckbill: load a bill file, look through its bills, pick some,
+//! and write them out again. Build a wheel with maturin; see pyproject.toml.
+//!
+//! Errors from the library become Python exceptions: AccountIdError for badly typed
+//! account IDs, BillFileError for data that isn't a usable bill file, OSError when a
+//! file can't be read or written, and DuckbillError (the base of the first two) for the rest.
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIndexError, PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyList, PySlice};
+
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckacctid::DuckAcctId;
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckcompress::DuckCompression;
+use duckbill::duckfile::duckerror::DuckError;
+use duckbill::duckfile::duckmeter::DuckMeterReading;
+
+create_exception!(duckbill, DuckbillError, PyException, "Something went wrong handling a bill file.");
+create_exception!(duckbill, BillFileError, DuckbillError, "The data isn't a usable bill file.");
+create_exception!(duckbill, AccountIdError, DuckbillError, "An account ID isn't valid; they look like 01-0123456-0.");
+
+/// Raise the Python exception matching a DuckError, with its message
+fn to_py_err(e: DuckError) -> PyErr {
+    let message = e.to_string();
+    match e {
+        DuckError::BadAccountIdFormat | DuckError::AccountIDTooShort => AccountIdError::new_err(message),
+        DuckError::IoError => PyOSError::new_err(message),
+        DuckError::BillCountOutOfBounds | DuckError::OpCancelled => DuckbillError::new_err(message),
+        _ => BillFileError::new_err(message),
+    }
+}
+
+/// Read a compression name ("gzip", "zip" or "none").
+/// Without one, go by the file name's extension, as justducky does.
+fn compression_of(name: Option<&str>, file_name: &str) -> PyResult<DuckCompression> {
+    match name.map(str::to_ascii_lowercase).as_deref() {
+        None => Ok(DuckCompression::from_file_name(file_name)),
+        Some("none") => Ok(DuckCompression::None),
+        Some("gzip") | Some("gz") => Ok(DuckCompression::Gzip),
+        Some("zip") => Ok(DuckCompression::Zip),
+        Some(other) => Err(PyValueError::new_err(format!("unknown compression \"{}\"; use \"gzip\", \"zip\" or \"none\"", other))),
+    }
+}
+
+/// Turn a Python index, which may count from the end, into a bill index
+fn bill_index(index: isize, count: usize) -> PyResult<usize> {
+    let from_start = if index < 0 { index + count as isize } else { index };
+    match usize::try_from(from_start) {
+        Ok(i) if i < count => Ok(i),
+        _ => Err(PyIndexError::new_err("bill index out of range")),
+    }
+}
+
+
+/// A customer account ID such as 01-0123456-0
+#[pyclass(name = "AccountId", module = "duckbill", frozen)]
+struct PyAccountId {
+    id: DuckAcctId,
+}
+
+#[pymethods]
+impl PyAccountId {
+    /// Parse an account ID. Dashes and spaces are optional, so "01 0123456 0" and
+    /// "0101234560" both give 01-0123456-0.
+    #[new]
+    fn new(text: &str) -> PyResult<Self> {
+        DuckAcctId::normalize(text).map(|id| PyAccountId { id }).map_err(to_py_err)
+    }
+
+    /// The ten digits of the ID, without dashes
+    #[getter]
+    fn digits(&self) -> String {
+        String::from_utf8_lossy(&self.id.get_digits()).into_owned()
+    }
+
+    fn __str__(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("AccountId('{}')", self.id)
+    }
+
+    fn __eq__(&self, other: PyRef<PyAccountId>) -> bool {
+        self.id == other.id
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.id.to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// An account to look for: an AccountId, or text to parse as one
+#[derive(FromPyObject)]
+enum AccountArg<'py> {
+    Id(PyRef<'py, PyAccountId>),
+    Text(String),
+}
+
+impl AccountArg<'_> {
+    fn to_acct_id(&self) -> PyResult<DuckAcctId> {
+        match self {
+            AccountArg::Id(id) => Ok(id.id.clone()),
+            AccountArg::Text(text) => DuckAcctId::normalize(text).map_err(to_py_err),
+        }
+    }
+}
+
+
+/// One row of a bill's meter readings table. Money amounts are in cents.
+#[pyclass(name = "MeterReading", module = "duckbill", frozen, get_all)]
+struct PyMeterReading {
+    meter_id: String,
+    previous_date: String,
+    previous_reading: u64,
+    present_date: String,
+    present_reading: u64,
+    code: String,
+    usage: u64,
+    water: Option<i64>,
+    sewer: Option<i64>,
+    total: i64,
+}
+
+impl From<DuckMeterReading> for PyMeterReading {
+    fn from(r: DuckMeterReading) -> Self {
+        PyMeterReading {
+            meter_id: r.meter_id,
+            previous_date: r.previous_date,
+            previous_reading: r.previous_reading,
+            present_date: r.present_date,
+            present_reading: r.present_reading,
+            code: r.code,
+            usage: r.usage,
+            water: r.water,
+            sewer: r.sewer,
+            total: r.total,
+        }
+    }
+}
+
+#[pymethods]
+impl PyMeterReading {
+    fn __repr__(&self) -> String {
+        format!("<MeterReading {} usage {} total {}>", self.meter_id, self.usage, self.total)
+    }
+}
+
+
+/// A single bill from a bill file
+#[pyclass(name = "Bill", module = "duckbill", frozen)]
+struct PyBill {
+    bill: DuckBill,
+}
+
+#[pymethods]
+impl PyBill {
+    #[getter]
+    fn account_id(&self) -> PyAccountId {
+        PyAccountId { id: self.bill.get_account_id().clone() }
+    }
+
+    #[getter]
+    fn bill_number(&self) -> u32 {
+        self.bill.get_bill_number()
+    }
+
+    /// The customer name on the payment stub, or None
+    #[getter]
+    fn name(&self) -> Option<String> {
+        self.bill.get_name()
+    }
+
+    /// The service address, one string per line
+    #[getter]
+    fn service_address(&self) -> Vec<String> {
+        self.bill.get_service_address()
+    }
+
+    /// The mailing address, one string per line; the first is normally the addressee
+    #[getter]
+    fn mailing_address(&self) -> Vec<String> {
+        self.bill.get_mailing_address()
+    }
+
+    /// The amount due in cents, or None if it couldn't be read
+    #[getter]
+    fn amount_due(&self) -> Option<i64> {
+        self.bill.get_amount_due()
+    }
+
+    #[getter]
+    fn meter_readings(&self) -> Vec<PyMeterReading> {
+        self.bill.get_meter_readings().into_iter().map(PyMeterReading::from).collect()
+    }
+
+    /// The printed text as lines, roughly as laid out on the page
+    #[getter]
+    fn plain_text(&self) -> Vec<String> {
+        self.bill.get_plain_text()
+    }
+
+    /// The bill's raw print data
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.bill.get_raw().as_ref())
+    }
+
+    fn __str__(&self) -> String {
+        self.bill.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Bill {} #{}>", self.bill.get_account_id(), self.bill.get_bill_number())
+    }
+}
+
+
+/// A bill file: a header, the bills, and a footer with the bill count.
+/// Bills are indexed from 0 and it can be sliced and iterated like a list.
+#[pyclass(name = "DuckFile", module = "duckbill", frozen)]
+struct PyDuckFile {
+    file: DuckFile,
+}
+
+impl PyDuckFile {
+    fn bill(&self, index: usize) -> PyBill {
+        PyBill { bill: self.file[index].clone() }
+    }
+
+    fn compressed(&self, compression: DuckCompression, entry_name: &str) -> PyResult<Vec<u8>> {
+        let mut data = Vec::new();
+        self.file.write_compressed_to(&mut data, compression, entry_name).map_err(to_py_err)?;
+        Ok(data)
+    }
+}
+
+#[pymethods]
+impl PyDuckFile {
+    /// Make a bill file holding the given bills, in order, or no bills at all
+    #[new]
+    #[pyo3(signature = (bills = None))]
+    fn new(bills: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let file = match bills {
+            None => DuckFile::new(),
+            Some(bills) => {
+                let bills = bills.try_iter()?
+                    .map(|b| Ok(b?.extract::<PyRef<PyBill>>()?.bill.clone()))
+                    .collect::<PyResult<Vec<DuckBill>>>()?;
+                DuckFile::try_from(bills).map_err(to_py_err)?
+            }
+        };
+        Ok(PyDuckFile { file })
+    }
+
+    /// Read a bill file; gzip and zip files are decompressed first
+    #[staticmethod]
+    fn open(path: PathBuf) -> PyResult<Self> {
+        let file = DuckFile::read_from(File::open(path)?).map_err(to_py_err)?;
+        Ok(PyDuckFile { file })
+    }
+
+    /// Read a bill file from bytes; gzip and zip data is decompressed first
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let file = DuckFile::read_from(data).map_err(to_py_err)?;
+        Ok(PyDuckFile { file })
+    }
+
+    fn __len__(&self) -> usize {
+        self.file.get_bill_count()
+    }
+
+    /// A bill by index, or a list of bills for a slice
+    fn __getitem__(&self, py: Python<'_>, index: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let count = self.file.get_bill_count();
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let range = slice.indices(count as isize)?;
+            let bills = (0..range.slicelength).map(|n| self.bill((range.start + n as isize * range.step) as usize));
+            return Ok(PyList::new(py, bills)?.into_any().unbind());
+        }
+        let bill = self.bill(bill_index(index.extract()?, count)?);
+        Ok(Py::new(py, bill)?.into_any())
+    }
+
+    fn __iter__(slf: Bound<'_, Self>) -> BillIterator {
+        BillIterator { file: slf.unbind(), next: 0 }
+    }
+
+    /// The index of the bill for an account (an AccountId, or text to parse as one), or None
+    fn index_of(&self, account: AccountArg) -> PyResult<Option<usize>> {
+        Ok(self.file.get_index_of_account(&account.to_acct_id()?))
+    }
+
+    /// The index of the bill with a bill number, or None
+    fn index_of_bill_number(&self, bill_number: u32) -> Option<usize> {
+        self.file.get_index_of_bill_number(bill_number)
+    }
+
+    /// A new bill file holding the bills at the given indexes, in the order given.
+    /// select(range(3, 7)) keeps bills 3 to 6.
+    fn select(&self, indexes: &Bound<'_, PyAny>) -> PyResult<PyDuckFile> {
+        let count = self.file.get_bill_count();
+        let bills = indexes.try_iter()?
+            .map(|i| Ok(self.file[bill_index(i?.extract()?, count)?].clone()))
+            .collect::<PyResult<Vec<DuckBill>>>()?;
+        let file = DuckFile::try_from(bills).map_err(to_py_err)?;
+        Ok(PyDuckFile { file })
+    }
+
+    /// The file as it would be saved. compression is "gzip", "zip" or "none";
+    /// a zip archive holds one entry called entry_name.
+    #[pyo3(signature = (compression = None, entry_name = "bills"))]
+    fn to_bytes<'py>(&self, py: Python<'py>, compression: Option<&str>, entry_name: &str) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.compressed(compression_of(compression, "")?, entry_name)?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Save the file. compression is "gzip", "zip" or "none"; without it, a path ending
+    /// in .gz or .zip is compressed to match.
+    #[pyo3(signature = (path, compression = None))]
+    fn write(&self, path: PathBuf, compression: Option<&str>) -> PyResult<()> {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let entry_name = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(&name);
+        let data = self.compressed(compression_of(compression, &name)?, entry_name)?;
+        std::fs::write(Path::new(&path), data)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<DuckFile with {} bills>", self.file.get_bill_count())
+    }
+}
+
+/// Steps through a DuckFile's bills
+#[pyclass(module = "duckbill")]
+struct BillIterator {
+    file: Py<PyDuckFile>,
+    next: usize,
+}
+
+#[pymethods]
+impl BillIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<PyBill> {
+        let file = self.file.get();
+        if self.next >= file.file.get_bill_count() {
+            return None;
+        }
+        self.next += 1;
+        Some(file.bill(self.next - 1))
+    }
+}
+
+
+#[pymodule]
+#[pyo3(name = "duckbill")]
+fn duckbill_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyDuckFile>()?;
+    m.add_class::<PyBill>()?;
+    m.add_class::<PyAccountId>()?;
+    m.add_class::<PyMeterReading>()?;
+    m.add("DuckbillError", m.py().get_type::<DuckbillError>())?;
+    m.add("BillFileError", m.py().get_type::<BillFileError>())?;
+    m.add("AccountIdError", m.py().get_type::<AccountIdError>())?;
+    Ok(())
+}
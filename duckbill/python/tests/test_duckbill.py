@@ -0,0 +1,118 @@
+"""Tests for the duckbill Python bindings, run against the anonymised fixture.
+
+run_tests.sh builds the module into a virtual environment and runs these. To run them
+by hand, build and install the module first (maturin develop), then run
+    python -m unittest discover -s tests
+"""
+import gzip
+import os
+import tempfile
+import unittest
+import zipfile
+from pathlib import Path
+
+import duckbill
+
+FIXTURE = Path(__file__).resolve().parents[2] / "test_data" / "anon_bill_file_4.dat"
+ACCOUNTS = ["52-1111111-1", "52-2222222-1", "52-3333333-1", "52-4444444-1"]
+
+
+class DuckFileTests(unittest.TestCase):
+    def setUp(self):
+        self.data = FIXTURE.read_bytes()
+        self.file = duckbill.DuckFile.open(FIXTURE)
+
+    def test_loads_and_iterates_bills(self):
+        self.assertEqual(len(self.file), 4)
+        self.assertEqual([str(bill.account_id) for bill in self.file], ACCOUNTS)
+        self.assertEqual([bill.bill_number for bill in self.file], [7488, 6671, 9648, 5956])
+        self.assertEqual(repr(self.file), "<DuckFile with 4 bills>")
+        self.assertEqual(duckbill.DuckFile.from_bytes(self.data).to_bytes(), self.data)
+
+    def test_indexes_and_slices(self):
+        self.assertEqual(self.file[-1].bill_number, 5956)
+        self.assertEqual([b.bill_number for b in self.file[1:3]], [6671, 9648])
+        self.assertEqual([b.bill_number for b in self.file[::-2]], [5956, 6671])
+        with self.assertRaises(IndexError):
+            self.file[4]
+
+    def test_bill_details(self):
+        bill = self.file[0]
+        self.assertEqual(bill.name, "NAME NUMBER 11")
+        self.assertEqual(bill.amount_due, 14521)
+        self.assertEqual(str(bill), "52-1111111-1 (Bill #7488)")
+        self.assertTrue(bill.service_address)
+        self.assertTrue(any("NAME NUMBER 11" in line for line in bill.plain_text))
+        for reading in bill.meter_readings:
+            self.assertIsInstance(reading.total, int)
+        self.assertIn(bill.to_bytes(), self.data)
+
+    def test_finds_bills(self):
+        self.assertEqual(self.file.index_of("52 3333333 1"), 2)
+        self.assertEqual(self.file.index_of(duckbill.AccountId("5244444441")), 3)
+        self.assertIsNone(self.file.index_of("01-0123456-0"))
+        self.assertEqual(self.file.index_of_bill_number(6671), 1)
+        self.assertIsNone(self.file.index_of_bill_number(1))
+
+    def test_selects_and_writes(self):
+        picked = self.file.select([3, 0])
+        self.assertEqual([b.bill_number for b in picked], [5956, 7488])
+        self.assertEqual(len(self.file.select(range(1, 3))), 2)
+        self.assertEqual(len(duckbill.DuckFile([self.file[2]])), 1)
+        # any iterable will do, not just lists
+        self.assertEqual([b.bill_number for b in self.file.select(i for i in (2, 1))], [9648, 6671])
+        self.assertEqual(len(duckbill.DuckFile(b for b in self.file if b.amount_due > 10000)), 2)
+        with self.assertRaises(TypeError):
+            duckbill.DuckFile([1, 2])
+        with self.assertRaises(IndexError):
+            self.file.select([9])
+
+        with tempfile.TemporaryDirectory() as folder:
+            plain = os.path.join(folder, "picked.DUCKED")
+            picked.write(plain)
+            self.assertEqual(Path(plain).read_bytes(), picked.to_bytes())
+
+            zipped = os.path.join(folder, "picked.zip")
+            picked.write(zipped)
+            with zipfile.ZipFile(zipped) as archive:
+                self.assertEqual(archive.namelist(), ["picked"])
+            self.assertEqual(len(duckbill.DuckFile.open(zipped)), 2)
+
+        self.assertEqual(gzip.decompress(picked.to_bytes("gzip")), picked.to_bytes())
+        with self.assertRaises(ValueError):
+            picked.to_bytes("rar")
+
+
+class AccountIdTests(unittest.TestCase):
+    def test_parses_loosely_typed_ids(self):
+        account = duckbill.AccountId(" 01 0123456 0 ")
+        self.assertEqual(str(account), "01-0123456-0")
+        self.assertEqual(account.digits, "0101234560")
+        self.assertEqual(repr(account), "AccountId('01-0123456-0')")
+        self.assertEqual(account, duckbill.AccountId("0101234560"))
+        self.assertNotEqual(account, "01-0123456-0")
+        self.assertEqual(len({account, duckbill.AccountId("01-0123456-0")}), 1)
+
+    def test_bad_ids_raise(self):
+        with self.assertRaises(duckbill.AccountIdError) as raised:
+            duckbill.AccountId("01-0123")
+        self.assertIn("too short", str(raised.exception))
+        self.assertRaises(duckbill.AccountIdError, duckbill.AccountId, "01-0123456-00")
+        self.assertTrue(issubclass(duckbill.AccountIdError, duckbill.DuckbillError))
+
+
+class ErrorTests(unittest.TestCase):
+    def test_bad_files_raise(self):
+        with self.assertRaises(duckbill.BillFileError) as raised:
+            duckbill.DuckFile.from_bytes(b"not a bill file")
+        self.assertEqual(str(raised.exception), "the file is too short to be a bill file")
+        self.assertRaises(duckbill.BillFileError, duckbill.DuckFile.from_bytes, b"x" * 5000)
+        self.assertTrue(issubclass(duckbill.BillFileError, duckbill.DuckbillError))
+
+    def test_missing_file_raises_os_error(self):
+        with self.assertRaises(FileNotFoundError):
+            duckbill.DuckFile.open("/no/such/bills.dat")
+
+
+if __name__ == "__main__":
+    unittest.main()
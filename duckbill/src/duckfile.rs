@@ -2,15 +2,26 @@
 //! Such a file contains a header, one or more bills, and a footer with bill count
 pub mod duckdata;
 pub mod duckacctid;
+pub mod duckanon;
 pub mod duckbill;
+pub mod duckbillbuilder;
+pub mod duckbulletin;
+pub mod duckcompress;
 pub mod duckerror;
+pub mod duckmeter;
+pub mod duckoverlay;
+pub mod duckpage;
+pub mod duckpcl;
+pub mod ducksearch;
 
 use std::fmt::{Debug, Formatter};
+use std::io::{Read, Write};
 use std::ops::Index;
 use std::slice::SliceIndex;
 use duckerror::DuckError;
 use crate::duckfile::duckbill::DuckBill;
 use duckdata::DuckData;
+use duckcompress::DuckCompression;
 use crate::duckfile::duckacctid::DuckAcctId;
 
 ///Any bill starts with these bytes
@@ -18,6 +29,11 @@ const RECORD_MARK_BYTES: &[u8;3] = &[0x1bu8, 0x45u8, 0x0du8];
 
 pub type DuckMark = usize;
 
+/// Smallest plausible bill file, in bytes
+pub const MIN_FILE_LEN: usize = 4000;
+/// Largest bill file that will be read, in bytes
+pub const MAX_FILE_LEN: usize = 100_000_000;
+
 pub struct DuckFile {
     header: DuckData,
     bills: Vec<DuckBill>,
@@ -80,17 +96,59 @@ impl DuckFile {
         }
     }
 
+    /// Read a whole bill file from any source, such as a file or stdin.
+    /// Gzip and zip input is decompressed first (see duckcompress).
+    /// Files under MIN_FILE_LEN or over MAX_FILE_LEN bytes, before or after decompression,
+    /// are refused; reading stops just past the maximum, so an endless source can't use up memory.
+    pub fn read_from<R: Read>(reader: R) -> Result<DuckFile, DuckError> {
+        let data = duckcompress::decompress(duckcompress::read_limited(reader)?)?;
+        if data.len() < MIN_FILE_LEN {
+            return Err(DuckError::FileTooSmall);
+        }
+        DuckFile::try_from(DuckData::from(data))
+    }
+
+    /// Write the bill file, exactly as it would be saved, to any destination such as a file or stdout
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), DuckError> {
+        writer.write_all(self.header.as_ref())?;
+        for bill in &self.bills {
+            writer.write_all(bill.get_raw().as_ref())?;
+        }
+        writer.write_all(self.footer.as_ref())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the bill file compressed; a zip archive holds one entry with the given name
+    pub fn write_compressed_to<W: Write>(&self, mut writer: W, compression: DuckCompression, entry_name: &str) -> Result<(), DuckError> {
+        let mut data = Vec::new();
+        self.write_to(&mut data)?;
+        writer.write_all(&duckcompress::compress(&data, compression, entry_name)?)?;
+        writer.flush()?;
+        Ok(())
+    }
+
     /// Get the number of bills found
     pub fn get_bill_count(&self) -> usize {
         self.bills.len()
     }
 
+    /// Take the bills out of the file, leaving its header and footer behind
+    pub fn into_bills(self) -> Vec<DuckBill> {
+        self.bills
+    }
+
     /// Get the index of a bill with the provided account number
     // TODO: this exposes too much of the inner workings; is there a better way?
     pub fn get_index_of_account(&self, acct: &DuckAcctId) -> Option<usize> {
         self.bills.iter().position(|a| a.get_account_id() == acct)
     }
 
+    /// Get the index of the bill with the provided bill number
+    pub fn get_index_of_bill_number(&self, bill_number: u32) -> Option<usize> {
+        self.bills.iter().position(|b| b.get_bill_number() == bill_number)
+    }
+
     /// Get the header associated with this bill file
     pub fn get_header(&self) -> &DuckData {
         &self.header
@@ -152,15 +210,15 @@ impl TryFrom<DuckData> for DuckFile {
         }
 
         //build vec of all bills
+        //a chunk holds at most one bill, however many times its account number is printed
         for m in 0..start_marks.len() - 1 {
-            for i in start_marks[m]..start_marks[m + 1] - duckacctid::ACCT_STR_BYTES_LEN {
-                if data[i..i + duckacctid::ACCT_STR_BYTES_LEN] == *duckacctid::ACCT_STR_BYTES {
-                    bills.push(DuckBill::new(data[start_marks[m]..start_marks[m + 1]].into())?)
-                }
+            let chunk = &data[start_marks[m]..start_marks[m + 1]];
+            if chunk.windows(duckacctid::ACCT_STR_BYTES_LEN).any(|w| w == duckacctid::ACCT_STR_BYTES) {
+                bills.push(DuckBill::new(chunk.into())?)
             }
         }
         let bill_count_bytes = data[data.len() - 7..data.len() - 1].to_owned();
-        let bill_count = String::from_utf8(bill_count_bytes.into())?.parse::<u32>()? as usize;
+        let bill_count = String::from_utf8(bill_count_bytes)?.parse::<u32>()? as usize;
 
         if bill_count != bills.len() || bill_count != start_marks.len() - 2 {
             return Err(DuckError::MarkCountMismatch);
@@ -284,6 +342,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bill_numbers_indexed() {
+        let quack = get_test_data();
+
+        assert_eq!(quack.get_index_of_bill_number(7488), Some(0));
+        assert_eq!(quack.get_index_of_bill_number(5956), Some(3));
+        assert_eq!(quack.get_index_of_bill_number(1234), None);
+    }
+
+    #[test]
+    fn read_and_write_streams() {
+        let quack = get_test_data();
+        let mut written = Vec::new();
+        quack.write_to(&mut written).unwrap();
+        let expected: DuckData = get_test_data().into();
+        assert_eq!(written, expected.as_ref());
+
+        let reread = DuckFile::read_from(&written[..]).unwrap();
+        assert_eq!(reread.get_bill_count(), 4);
+
+        assert_eq!(DuckFile::read_from(&written[..MIN_FILE_LEN - 1]).unwrap_err(), DuckError::FileTooSmall);
+        let endless = std::io::repeat(b'Q');
+        assert_eq!(DuckFile::read_from(endless).unwrap_err(), DuckError::FileTooBig);
+    }
+
     #[test]
     fn header_is_sane() {
         let _quack = get_test_data();
@@ -310,6 +393,43 @@ mod tests {
         assert_eq!(broken.unwrap_err(), DuckError::MarkCountMismatch);
     }
 
+    #[test]
+    fn hostile_input_never_panics() {
+        use crate::duckfile::duckbillbuilder::DuckRng;
+
+        let original: DuckData = get_test_data().into();
+        let original: Vec<u8> = original.into();
+        let nasty: [&[u8]; 4] = [RECORD_MARK_BYTES, duckacctid::ACCT_STR_BYTES, b"BILL #:    \x1b&a0405v0825H", b"\x1b&a3645v0970H"];
+        let mut rng = DuckRng::new(0xd0c);
+
+        for _ in 0..300 {
+            let mut data = original.clone();
+            for _ in 0..1 + rng.below(4) {
+                let at = rng.below(data.len() as u64) as usize;
+                match rng.below(4) {
+                    0 => data[at] = rng.below(256) as u8,
+                    1 => data.truncate(at.max(1)),
+                    2 => {
+                        let piece = nasty[rng.below(nasty.len() as u64) as usize];
+                        data.splice(at..at, piece.iter().copied());
+                    }
+                    _ => {
+                        let end = (at + rng.below(64) as usize).min(data.len());
+                        data.drain(at..end);
+                    }
+                }
+            }
+
+            if let Ok(quack) = DuckFile::try_from(DuckData::new(data)) {
+                for bill in quack[..].iter() {
+                    let _ = bill.get_name();
+                    let _ = bill.get_service_address();
+                    let _ = bill.get_mailing_address();
+                }
+            }
+        }
+    }
+
     #[test]
     fn static_header_len() {
         assert_eq!(DuckFile::get_static_header().len(), DuckFile::NOMINAL_HEADER_LEN);
@@ -1,5 +1,6 @@
 //! DuckAcctId constrains a string of bytes to ensure it is a value account ID
 
+use std::fmt::{Display, Formatter};
 use super::{DuckData, DuckError};
 
 /// The base structure
@@ -14,6 +15,8 @@ pub const ACCT_STR_BYTES: &[u8] = b"Acct No: ";
 pub const ACCT_STR_BYTES_LEN: usize = ACCT_STR_BYTES.len();
 /// length of an account number byte sequence
 pub const ACCT_NUMBER_LEN: usize = b"01-0123456-0".len();
+/// number of digits in an account number
+pub const ACCT_DIGITS_LEN: usize = b"0101234560".len();
 /// length of the entire byte sequence
 pub const ACCT_MARK_LEN: usize = ACCT_STR_BYTES_LEN + ACCT_NUMBER_LEN;
 
@@ -24,6 +27,9 @@ impl DuckAcctId {
     /// valid IDs are of the format 01-0123456-7
     pub fn validate(id: &DuckData) -> bool {
         //format is 01-0123456-0
+        if id.len() != ACCT_NUMBER_LEN {
+            return false;
+        }
         for &p in [0, 1, 3, 4, 5, 6, 7, 8, 9, 11].iter() {
             if !DuckData::is_ascii_number(id[p]) {
                 return false;
@@ -32,17 +38,46 @@ impl DuckAcctId {
 
         id[2] == b'-' && id[10] == b'-'
     }
+
+    /// normalize loosely typed input into a canonical ID.
+    /// dashes and whitespace are ignored, so "01 0123456 0" and "0101234560"
+    /// both become 01-0123456-0
+    pub fn normalize(input: &str) -> Result<DuckAcctId, DuckError> {
+        let digits: Vec<u8> = input.bytes()
+            .filter(|b| *b != b'-' && !b.is_ascii_whitespace())
+            .collect();
+        if digits.len() < ACCT_DIGITS_LEN {
+            return Err(DuckError::AccountIDTooShort);
+        }
+        if digits.len() > ACCT_DIGITS_LEN {
+            return Err(DuckError::BadAccountIdFormat);
+        }
+
+        let mut canonical = Vec::with_capacity(ACCT_NUMBER_LEN);
+        canonical.extend_from_slice(&digits[0..2]);
+        canonical.push(b'-');
+        canonical.extend_from_slice(&digits[2..9]);
+        canonical.push(b'-');
+        canonical.push(digits[9]);
+        canonical.try_into()
+    }
+
+    /// get only the digits of the ID, without dashes
+    pub fn get_digits(&self) -> Vec<u8> {
+        self.my_data.iter().copied().filter(|b| DuckData::is_ascii_number(*b)).collect()
+    }
 }
 
-impl ToString for DuckAcctId {
-    fn to_string(&self) -> String {
-        String::from_utf8(self.my_data.clone()).unwrap_or_else(|_| {
+impl Display for DuckAcctId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = String::from_utf8(self.my_data.clone()).unwrap_or_else(|_| {
             self.my_data
                 .iter()
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>()
                 .join(",")
-        })
+        });
+        write!(f, "{}", s)
     }
 }
 
@@ -74,6 +109,14 @@ impl TryFrom<DuckData> for DuckAcctId {
     }
 }
 
+/// normalize typed input; see DuckAcctId::normalize
+impl TryFrom<String> for DuckAcctId {
+    type Error = DuckError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        DuckAcctId::normalize(&value)
+    }
+}
+
 /// convert to DuckData sequence
 impl From<DuckAcctId> for DuckData {
     fn from(id: DuckAcctId) -> Self {
@@ -131,6 +174,11 @@ mod tests {
 
         let bad_digit: DuckData = "01-01Z3456-0".into();
         assert!(! DuckAcctId::validate(&bad_digit));
+
+        let too_long: DuckData = "01-0123456-01".into();
+        assert!(! DuckAcctId::validate(&too_long));
+
+        assert!(! DuckAcctId::validate(&DuckData::new(vec![])));
     }
 
     #[test]
@@ -145,4 +193,22 @@ mod tests {
 
         assert!(bad_id_res.is_err());
     }
+
+    #[test]
+    fn normalize_accepts_loose_input() {
+        let canonical: DuckAcctId = DuckData::from("01-0123456-0").try_into().unwrap();
+
+        for loose in ["01-0123456-0", "0101234560", "01 0123456 0", " 01-0123 456-0 "] {
+            assert_eq!(DuckAcctId::normalize(loose), Ok(canonical.clone()));
+        }
+    }
+
+    #[test]
+    fn normalize_rejects_bad_input() {
+        assert_eq!(DuckAcctId::normalize("01-01234"), Err(DuckError::AccountIDTooShort));
+        assert_eq!(DuckAcctId::normalize("01-0123456-01"), Err(DuckError::BadAccountIdFormat));
+        assert_eq!(DuckAcctId::normalize("01-01Z3456-0"), Err(DuckError::BadAccountIdFormat));
+        let typed = DuckAcctId::try_from(String::from("01-01Z3456-0")).unwrap_err();
+        assert_eq!(typed.to_string(), "account IDs look like 01-0123456-0");
+    }
 }
@@ -0,0 +1,286 @@
+//! Replace personal details in a bill file with consistent fake values.
+//! The result keeps every PCL command and cursor position exactly where it was,
+//! so it can be shared as a test fixture and still parses as a DuckFile.
+use std::collections::HashMap;
+use std::ops::Range;
+use super::DuckFile;
+use super::duckacctid::DuckAcctId;
+use super::duckbill::{DuckBill, MAILING_ADDR_POS_BYTES, NAME_POS_BYTES, SERVICE_ADDR_POS_BYTES};
+use super::duckdata::DuckData;
+use super::duckerror::DuckError;
+
+/// Cursor position of the service address near the top of the bill
+const TOP_SERVICE_ADDR_POS_BYTES: &[u8] = b"\x1b&a0645v3500H";
+/// Cursor position of the phone number next to the bill number
+const PHONE_POS_BYTES: &[u8] = b"\x1b&a0405v2225H";
+
+pub(crate) const FAKE_FIRST_NAMES: [&str; 8] = ["ALEX", "JORDAN", "TAYLOR", "MORGAN", "CASEY", "RILEY", "JAMIE", "AVERY"];
+pub(crate) const FAKE_LAST_NAMES: [&str; 8] = ["SMITH", "JONES", "BROWN", "MILLER", "DAVIS", "WILSON", "MOORE", "CLARK"];
+pub(crate) const FAKE_STREETS: [&str; 8] = ["MAPLE", "OAK", "PINE", "ELM", "CEDAR", "BIRCH", "WALNUT", "SPRUCE"];
+pub(crate) const FAKE_TOWNS: [&str; 4] = ["ANYTOWN NY", "SPRINGFIELD MA", "FAIRVIEW PA", "RIVERSIDE VT"];
+
+/// Which kind of fake value replaces a field
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+enum AnonKind {
+    Name,
+    Street,
+    Town,
+    Phone,
+}
+
+/// Replaces names, addresses, account IDs, bill numbers, phone numbers and barcode payloads.
+/// The same real value always gets the same fake value, across every bill passed
+/// through one DuckAnonymizer, so a customer's name on the stub still matches
+/// the name in their mailing address.
+#[derive(Default)]
+pub struct DuckAnonymizer {
+    text: HashMap<(AnonKind, String), String>,
+    /// how many fake values of each kind have been made so far
+    text_counts: HashMap<AnonKind, usize>,
+    accounts: HashMap<Vec<u8>, Vec<u8>>,
+    bill_numbers: HashMap<u32, u32>,
+}
+
+impl DuckAnonymizer {
+    pub fn new() -> DuckAnonymizer {
+        DuckAnonymizer::default()
+    }
+
+    /// Produce an anonymized copy of a whole bill file
+    pub fn anonymize_file(&mut self, file: &DuckFile) -> Result<DuckFile, DuckError> {
+        let bills = file[..].iter()
+            .map(|b| self.anonymize_bill(b))
+            .collect::<Result<Vec<DuckBill>, DuckError>>()?;
+        DuckFile::try_from(bills)
+    }
+
+    /// Produce an anonymized copy of one bill.
+    /// The copy has exactly the same length as the original.
+    pub fn anonymize_bill(&mut self, bill: &DuckBill) -> Result<DuckBill, DuckError> {
+        let mut raw: Vec<u8> = bill.get_raw().as_ref().to_vec();
+        let mut fields: Vec<(AnonKind, Range<usize>)> = vec![];
+
+        for range in bill.field_ranges(NAME_POS_BYTES) {
+            fields.push((AnonKind::Name, range));
+        }
+        for marker in SERVICE_ADDR_POS_BYTES.iter().chain([TOP_SERVICE_ADDR_POS_BYTES].iter()) {
+            for range in bill.field_ranges(marker) {
+                fields.push((AnonKind::Street, range));
+            }
+        }
+        for (line, marker) in MAILING_ADDR_POS_BYTES.iter().enumerate() {
+            for range in bill.field_ranges(marker) {
+                let kind = if line == 0 {
+                    AnonKind::Name
+                } else if ends_with_zip(&raw[range.clone()]) {
+                    AnonKind::Town
+                } else {
+                    AnonKind::Street
+                };
+                fields.push((kind, range));
+            }
+        }
+        for range in bill.field_ranges(PHONE_POS_BYTES) {
+            fields.push((AnonKind::Phone, range));
+        }
+
+        for (kind, range) in fields {
+            let original = String::from_utf8_lossy(&raw[range.clone()]).trim_end().to_string();
+            if original.is_empty() {
+                continue;
+            }
+            let fake = self.fake_text(kind, &original);
+            raw[range.clone()].copy_from_slice(&fit(&fake, range.len()));
+        }
+
+        // account IDs and bill numbers appear in several places, including
+        // inside the barcode payload, so every occurrence is replaced
+        let account = bill.get_account_id().to_string().into_bytes();
+        let fake_account = self.fake_account(&account);
+        replace_all(&mut raw, &account, &fake_account);
+
+        let bill_number = format!("{:07}", bill.get_bill_number()).into_bytes();
+        let fake_bill_number = format!("{:07}", self.fake_bill_number(bill.get_bill_number())).into_bytes();
+        replace_all(&mut raw, &bill_number, &fake_bill_number);
+
+        DuckBill::new(DuckData::new(raw))
+    }
+
+    /// Get the fake text for a real value, inventing one if this value is new
+    fn fake_text(&mut self, kind: AnonKind, original: &str) -> String {
+        let counts = &mut self.text_counts;
+        self.text.entry((kind, original.to_string()))
+            .or_insert_with(|| {
+                let count = counts.entry(kind).or_insert(0);
+                let n = *count;
+                *count += 1;
+                match kind {
+                    AnonKind::Name => {
+                        let mut name = format!("{} {}", FAKE_FIRST_NAMES[n % 8], FAKE_LAST_NAMES[(n / 8) % 8]);
+                        if n >= 64 {
+                            name.push_str(&format!(" {}", n / 64));
+                        }
+                        name
+                    },
+                    AnonKind::Street => format!("{} {} ST", 100 + n, FAKE_STREETS[n % 8]),
+                    AnonKind::Town => format!("{}  {:05}", FAKE_TOWNS[n % 4], n % 100_000),
+                    AnonKind::Phone => fake_digits(original, 5_550_000_000 + n as u64),
+                }
+            })
+            .clone()
+    }
+
+    /// Get the fake account ID for a real one, keeping the 01-0123456-0 format
+    fn fake_account(&mut self, original: &[u8]) -> Vec<u8> {
+        let n = self.accounts.len() + 1;
+        self.accounts.entry(original.to_vec())
+            .or_insert_with(|| {
+                let fake = format!("99-{:07}-{}", n % 10_000_000, n % 10);
+                debug_assert!(DuckAcctId::validate(&DuckData::from(fake.as_str())));
+                fake.into_bytes()
+            })
+            .clone()
+    }
+
+    /// Get the fake bill number for a real one
+    fn fake_bill_number(&mut self, original: u32) -> u32 {
+        let n = self.bill_numbers.len() as u32 + 1;
+        *self.bill_numbers.entry(original).or_insert(n % 10_000_000)
+    }
+}
+
+/// Pad with spaces or truncate text to exactly `width` bytes
+fn fit(text: &str, width: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = text.bytes().take(width).collect();
+    bytes.resize(width, b' ');
+    bytes
+}
+
+/// Keep the punctuation of a pattern such as "(315) 785-7757", replacing its digits
+/// with the trailing digits of `number`
+fn fake_digits(pattern: &str, number: u64) -> String {
+    let slots = pattern.bytes().filter(|b| b.is_ascii_digit()).count();
+    let digits = format!("{:0>width$}", number, width = slots);
+    let mut fill = digits[digits.len() - slots..].chars();
+    pattern.chars()
+        .map(|c| if c.is_ascii_digit() { fill.next().unwrap_or('0') } else { c })
+        .collect()
+}
+
+/// Test if an address line ends in a ZIP or ZIP+4 code, as a city/state line does
+fn ends_with_zip(line: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(line);
+    let last = text.split_whitespace().last().unwrap_or("");
+    let mut parts = last.split('-');
+    let zip = parts.next().unwrap_or("");
+    let plus4 = parts.next();
+    zip.len() == 5 && zip.bytes().all(|b| b.is_ascii_digit())
+        && plus4.is_none_or(|p| p.len() == 4 && p.bytes().all(|b| b.is_ascii_digit()))
+        && parts.next().is_none()
+}
+
+/// Replace every occurrence of `from` with `to`, which must be the same length
+fn replace_all(data: &mut [u8], from: &[u8], to: &[u8]) {
+    debug_assert_eq!(from.len(), to.len());
+    let mut i = 0;
+    while i + from.len() <= data.len() {
+        if &data[i..i + from.len()] == from {
+            data[i..i + from.len()].copy_from_slice(to);
+            i += from.len();
+        } else {
+            i += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::tests::get_test_data;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn anonymized_file_still_parses() {
+        let quack = get_test_data();
+        let anon = DuckAnonymizer::new().anonymize_file(&quack).unwrap();
+
+        assert_eq!(anon.get_bill_count(), quack.get_bill_count());
+        for i in 0..quack.get_bill_count() {
+            assert_eq!(anon[i].get_raw().len(), quack[i].get_raw().len());
+        }
+    }
+
+    #[test]
+    fn pcl_commands_untouched() {
+        let quack = get_test_data();
+        let anon = DuckAnonymizer::new().anonymize_file(&quack).unwrap();
+
+        for i in 0..quack.get_bill_count() {
+            let before = quack[i].get_raw().as_ref();
+            let after = anon[i].get_raw().as_ref();
+            for (p, &b) in before.iter().enumerate() {
+                if b == 0x1b || b == 0x0d {
+                    assert_eq!(after[p], b);
+                    // the command letters following an escape are kept too
+                    assert_eq!(after.get(p..p + 4), before.get(p..p + 4));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn personal_details_removed() {
+        let quack = get_test_data();
+        let anon = DuckAnonymizer::new().anonymize_file(&quack).unwrap();
+        let anon_data: DuckData = anon.into();
+
+        for b in quack[..].iter() {
+            let mut secrets: Vec<String> = vec![b.get_account_id().to_string(), format!("{:07}", b.get_bill_number())];
+            secrets.extend(b.get_name());
+            secrets.extend(b.get_service_address());
+            secrets.extend(b.get_mailing_address());
+            for secret in secrets {
+                assert!(!contains(anon_data.as_ref(), secret.as_bytes()), "{} leaked", secret);
+            }
+        }
+        assert!(!contains(anon_data.as_ref(), b"785-7757"));
+    }
+
+    #[test]
+    fn fake_values_are_consistent() {
+        let quack = get_test_data();
+        let mut anonymizer = DuckAnonymizer::new();
+        let anon = anonymizer.anonymize_file(&quack).unwrap();
+        let again = anonymizer.anonymize_bill(&quack[2]).unwrap();
+
+        assert_eq!(again, anon[2]);
+        for b in anon[..].iter() {
+            assert_eq!(b.get_name().as_ref(), b.get_mailing_address().first());
+            let barcode = format!("*202152{:07}*", b.get_bill_number());
+            assert!(contains(b.get_raw().as_ref(), barcode.as_bytes()));
+        }
+        assert_ne!(anon[0].get_account_id(), anon[1].get_account_id());
+    }
+
+    #[test]
+    fn fit_pads_and_truncates() {
+        assert_eq!(fit("ABC", 5), b"ABC  ");
+        assert_eq!(fit("ABCDEF", 3), b"ABC");
+    }
+
+    #[test]
+    fn fake_digits_keep_format() {
+        assert_eq!(fake_digits("(315) 785-7757", 5_550_000_001), "(555) 000-0001");
+    }
+
+    #[test]
+    fn zip_lines_detected() {
+        assert!(ends_with_zip(b"AMHERST MA  01002"));
+        assert!(ends_with_zip(b"STERLING MA  01564-2724"));
+        assert!(!ends_with_zip(b"111 ADDR LINE 1ST"));
+    }
+}
@@ -1,7 +1,11 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use super::duckacctid;
 use super::duckacctid::DuckAcctId;
 use super::duckdata::DuckData;
 use super::duckerror::DuckError;
+use super::duckmeter::{parse_amount, DuckMeterReading};
+use super::duckpcl::{plain_text, PclToken, PclTokens, CR, ESC};
 
 pub type DuckResult<T> = std::result::Result<T, DuckError>;
 pub type DuckBillMap = std::collections::HashMap<Vec<u8>, (usize, usize)>;
@@ -11,6 +15,18 @@ const BILLNUM_STR_BYTES: &[u8] = b"BILL #:    \x1b&a0405v0825H";
 const BILLNUM_STR_BYTES_LEN: usize = BILLNUM_STR_BYTES.len();
 const BILLNUM_LEN: usize = b"0123456".len();
 
+/// Cursor position that precedes the customer name on the payment stub
+pub(crate) const NAME_POS_BYTES: &[u8] = b"\x1b&a3645v0970H";
+/// Cursor positions of the two service address lines on the payment stub
+pub(crate) const SERVICE_ADDR_POS_BYTES: [&[u8]; 2] = [b"\x1b&a3405v0970H", b"\x1b&a3525v0970H"];
+/// Cursor position of the amount due by the due date on the payment stub
+pub(crate) const AMOUNT_DUE_POS_BYTES: &[u8] = b"\x1b&a3885v2240H";
+/// Cursor positions of the mailing address block lines, addressee first
+pub(crate) const MAILING_ADDR_POS_BYTES: [&[u8]; 5] = [
+    b"\x1b&a6455v2220H", b"\x1b&a6575v2220H", b"\x1b&a6695v2220H",
+    b"\x1b&a6815v2220H", b"\x1b&a6935v2220H",
+];
+
 #[derive(PartialEq, Debug)]
 pub struct DuckBill {
     raw_data: DuckData,
@@ -34,11 +50,87 @@ impl DuckBill {
     pub fn get_raw(&self) -> &DuckData {
         &self.raw_data
     }
+
+    /// Get mutable access to the raw bytes for in-place edits.
+    /// Edits must not touch the account ID or bill number, which are parsed once on creation.
+    pub(crate) fn get_raw_mut(&mut self) -> &mut [u8] {
+        self.raw_data.as_mut()
+    }
+
+    /// Get the customer name printed on the payment stub, if present
+    pub fn get_name(&self) -> Option<String> {
+        self.find_field(NAME_POS_BYTES)
+    }
+
+    /// Get the service address printed on the payment stub, one entry per non-blank line
+    pub fn get_service_address(&self) -> Vec<String> {
+        self.find_fields(&SERVICE_ADDR_POS_BYTES)
+    }
+
+    /// Get the mailing address block, one entry per non-blank line.
+    /// The first line is normally the addressee.
+    pub fn get_mailing_address(&self) -> Vec<String> {
+        self.find_fields(&MAILING_ADDR_POS_BYTES)
+    }
+
+    /// Get the amount due by the due date printed on the payment stub, in cents
+    pub fn get_amount_due(&self) -> Option<i64> {
+        parse_amount(&self.find_field(AMOUNT_DUE_POS_BYTES)?)
+    }
+
+    /// Get the rows of the meter readings table
+    pub fn get_meter_readings(&self) -> Vec<DuckMeterReading> {
+        let raw = self.raw_data.as_ref();
+        PclTokens::new(raw)
+            .filter_map(|token| match token {
+                PclToken::Text(range) => DuckMeterReading::from_line(&String::from_utf8_lossy(&raw[range])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the bill's printed text as plain text lines, roughly as laid out on the page
+    pub fn get_plain_text(&self) -> Vec<String> {
+        plain_text(self.raw_data.as_ref())
+    }
+
+    /// Find the non-blank text printed after each of several cursor positioning sequences
+    fn find_fields(&self, markers: &[&[u8]]) -> Vec<String> {
+        markers.iter()
+            .filter_map(|m| self.find_field(m))
+            .filter(|f| !f.is_empty())
+            .collect()
+    }
+
+    /// Find the text printed after a cursor positioning sequence.
+    /// The text runs until the next escape sequence or carriage return,
+    /// and is returned with trailing padding removed.
+    fn find_field(&self, marker: &[u8]) -> Option<String> {
+        let range = self.field_ranges(marker).into_iter().next()?;
+        let text = String::from_utf8_lossy(&self.raw_data[range]);
+        Some(text.trim_end().to_string())
+    }
+
+    /// Find the byte ranges of all text printed after a cursor positioning sequence,
+    /// including any padding. Replacing a range with the same number of bytes
+    /// leaves all PCL commands where they were.
+    pub(crate) fn field_ranges(&self, marker: &[u8]) -> Vec<Range<usize>> {
+        let raw = self.raw_data.as_ref();
+        let mut ranges = vec![];
+        let mut from = 0;
+        while let Some(pos) = raw[from..].windows(marker.len()).position(|w| w == marker) {
+            let start = from + pos + marker.len();
+            let len = raw[start..].iter().position(|&b| b == ESC || b == CR).unwrap_or(raw.len() - start);
+            ranges.push(start..start + len);
+            from = start + len;
+        }
+        ranges
+    }
 }
 
-impl ToString for DuckBill {
-    fn to_string(&self) -> String {
-        todo!()
+impl Display for DuckBill {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (Bill #{})", self.account_id, self.bill_number)
     }
 }
 
@@ -48,18 +140,21 @@ impl TryFrom<DuckData> for DuckBill {
     fn try_from(raw_data: DuckData) -> Result<Self, Self::Error> {
         let mut account_id_maybe = None;
         let mut bill_num_maybe = None;
-        for i in 0..raw_data.len() - BILLNUM_STR_BYTES_LEN {
-            if raw_data[i..i + duckacctid::ACCT_STR_BYTES_LEN] == *duckacctid::ACCT_STR_BYTES {
+        let raw = raw_data.as_ref();
+        for i in 0..raw.len() {
+            if raw[i..].starts_with(duckacctid::ACCT_STR_BYTES) {
+                let id_start = i + duckacctid::ACCT_STR_BYTES_LEN;
                 account_id_maybe = Some(
-                    raw_data[i + duckacctid::ACCT_STR_BYTES_LEN
-                        ..i + duckacctid::ACCT_STR_BYTES_LEN + duckacctid::ACCT_NUMBER_LEN]
+                    raw.get(id_start..id_start + duckacctid::ACCT_NUMBER_LEN)
+                        .ok_or(DuckError::AccountIDTooShort)?
                         .to_owned(),
                 );
             }
-            if raw_data[i..i + BILLNUM_STR_BYTES_LEN] == *BILLNUM_STR_BYTES {
+            if raw[i..].starts_with(BILLNUM_STR_BYTES) {
+                let num_start = i + BILLNUM_STR_BYTES_LEN;
                 bill_num_maybe = Some(
-                    raw_data
-                        [i + BILLNUM_STR_BYTES_LEN ..i + BILLNUM_STR_BYTES_LEN + BILLNUM_LEN]
+                    raw.get(num_start..num_start + BILLNUM_LEN)
+                        .ok_or(DuckError::BadBillNumberFormat)?
                         .to_owned(),
                 );
             }
@@ -67,16 +162,11 @@ impl TryFrom<DuckData> for DuckBill {
 
         match (account_id_maybe, bill_num_maybe) {
             (Some(account_id), Some(bill_number_bytes)) => {
-                let bill_num_str = String::from_utf8(bill_number_bytes);
-                let bill_number = if bill_num_str.is_ok() {
-                    let bill_num_parse = bill_num_str.unwrap().parse::<u32>();
-                    if let Ok(bill_num) = bill_num_parse {
-                        Ok(bill_num)
-                    } else {
-                        Err(DuckError::BadBillNumberFormat)
-                    }
-                } else {
-                    Err(DuckError::BadNumberData)
+                let bill_number = match String::from_utf8(bill_number_bytes) {
+                    Ok(bill_num_str) => bill_num_str
+                        .parse::<u32>()
+                        .map_err(|_| DuckError::BadBillNumberFormat),
+                    Err(_) => Err(DuckError::BadNumberData),
                 };
 
                 Ok(DuckBill {
@@ -132,4 +222,66 @@ mod tests {
         let test_data = get_test_data();
         assert_eq!(test_data.bills[0], test_data.bills[0].clone());
     }
+
+    #[test]
+    fn names_found() {
+        let test_data = get_test_data();
+        assert_eq!(test_data[0].get_name(), Some("NAME NUMBER 11".to_string()));
+        assert_eq!(test_data[2].get_name(), Some("NAME THREE THREE33".to_string()));
+    }
+
+    #[test]
+    fn addresses_found() {
+        let test_data = get_test_data();
+        assert_eq!(test_data[0].get_service_address(), vec!["123 BILLONE ST"]);
+        assert_eq!(test_data[1].get_mailing_address(),
+                   vec!["NAME NUMBER 22", "ADDR LINE 2 22", "STERLING MA  01564-2724"]);
+    }
+
+    #[test]
+    fn amounts_due_found() {
+        let test_data = get_test_data();
+        let due: Vec<Option<i64>> = test_data[..].iter().map(|b| b.get_amount_due()).collect();
+        assert_eq!(due, [Some(14521), Some(6915), Some(236), Some(11650)]);
+    }
+
+    #[test]
+    fn meter_readings_found() {
+        let test_data = get_test_data();
+        let readings = test_data[0].get_meter_readings();
+        assert_eq!(readings.len(), 1);
+        assert_eq!((readings[0].meter_id.as_str(), readings[0].usage, readings[0].total), ("001", 6, 6915));
+    }
+
+    #[test]
+    fn plain_text_shows_the_stub() {
+        let text = get_test_data()[0].get_plain_text();
+        assert!(text.iter().any(|line| line.contains("Account #:") && line.contains("52-1111111-1")));
+        assert!(text.iter().any(|line| line.trim_start().starts_with("001  1/04/21")));
+    }
+
+    #[test]
+    fn short_records_are_errors() {
+        use crate::duckfile::duckdata::DuckData;
+        use crate::duckfile::duckerror::DuckError;
+        use super::DuckBill;
+
+        assert_eq!(DuckBill::new(DuckData::new(vec![])), Err(DuckError::BadIdentifierData));
+        assert_eq!(DuckBill::new("BILL #:".into()), Err(DuckError::BadIdentifierData));
+        assert_eq!(DuckBill::new("Acct No: 52-11".into()), Err(DuckError::AccountIDTooShort));
+        assert_eq!(DuckBill::new("BILL #:    \x1b&a0405v0825H00074".into()), Err(DuckError::BadBillNumberFormat));
+
+        // every truncation of a real bill must be handled without panicking
+        let test_data = get_test_data();
+        let raw = test_data[0].get_raw().as_ref();
+        for len in 0..raw.len() {
+            let _ = DuckBill::new(raw[..len].into());
+        }
+    }
+
+    #[test]
+    fn display_shows_account_and_bill_number() {
+        let test_data = get_test_data();
+        assert_eq!(test_data[0].to_string(), "52-1111111-1 (Bill #7488)");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,462 @@
+//! Build well-formed bill records from typed values.
+//! Tests and benchmarks can make bills and files of any size or shape
+//! without needing a real bill run.
+use super::DuckFile;
+use super::duckacctid::DuckAcctId;
+use super::duckanon::{FAKE_FIRST_NAMES, FAKE_LAST_NAMES, FAKE_STREETS, FAKE_TOWNS};
+use super::duckbill::{DuckBill, DuckResult};
+use super::duckdata::DuckData;
+use super::duckerror::DuckError;
+use super::duckmeter::{format_amount, DuckMeterReading};
+
+/// Rows in the meter readings table; unused rows are printed blank
+const METER_TABLE_ROWS: usize = 6;
+/// Width of the meter readings table, which the amount column lines up with
+const METER_TABLE_WIDTH: usize = 91;
+/// Width of the service address field at the top of the bill
+const TOP_SERVICE_ADDR_WIDTH: usize = 30;
+/// Width of each bulletin message line
+pub const BULLETIN_WIDTH: usize = 48;
+/// Number of bulletin message lines
+pub const BULLETIN_LINES: usize = 4;
+/// Width of the amount due fields on the payment stub
+const STUB_AMOUNT_WIDTH: usize = 15;
+/// Largest bill number that fits the seven digits bills print
+const MAX_BILL_NUMBER: u32 = 9_999_999;
+
+/// Builds a single DuckBill.
+/// Only the account ID and bill number are required; everything else has a plausible default.
+#[derive(Clone)]
+pub struct DuckBillBuilder {
+    account_id: DuckAcctId,
+    bill_number: u32,
+    bill_date: String,
+    due_date: String,
+    phone: String,
+    name: String,
+    service_address: Vec<String>,
+    mailing_address: Option<Vec<String>>,
+    bulletin: [String; BULLETIN_LINES],
+    meters: Vec<DuckMeterReading>,
+    previous_balance: i64,
+    current_charges: i64,
+    amount_due: Option<i64>,
+    amount_due_after: Option<i64>,
+    barcode: Option<String>,
+}
+
+impl DuckBillBuilder {
+    pub fn new(account_id: DuckAcctId, bill_number: u32) -> DuckBillBuilder {
+        DuckBillBuilder {
+            account_id,
+            bill_number,
+            bill_date: "04/30/21".to_string(),
+            due_date: "05/20/21".to_string(),
+            phone: "(315) 785-7757".to_string(),
+            name: "CUSTOMER".to_string(),
+            service_address: vec!["1 MAIN ST".to_string()],
+            mailing_address: None,
+            bulletin: [
+                "PAYMENTS MAY BE DROPPED OF IN".to_string(),
+                "SILVER LOCKBOX ON STERLING ST".to_string(),
+                "WATER FLUSHING WILL BEGIN".to_string(),
+                "MAY 9, 2021".to_string(),
+            ],
+            meters: vec![],
+            previous_balance: 0,
+            current_charges: 0,
+            amount_due: None,
+            amount_due_after: None,
+            barcode: None,
+        }
+    }
+
+    /// Set the bill date, formatted MM/DD/YY
+    pub fn bill_date(mut self, date: &str) -> Self {
+        self.bill_date = date.to_string();
+        self
+    }
+
+    /// Set the due date, formatted MM/DD/YY
+    pub fn due_date(mut self, date: &str) -> Self {
+        self.due_date = date.to_string();
+        self
+    }
+
+    /// Set the office phone number printed beside the bill number
+    pub fn phone(mut self, phone: &str) -> Self {
+        self.phone = phone.to_string();
+        self
+    }
+
+    /// Set the customer name
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the service address; only the first two lines are printed
+    pub fn service_address(mut self, lines: &[&str]) -> Self {
+        self.service_address = lines.iter().take(2).map(|l| l.to_string()).collect();
+        self
+    }
+
+    /// Set the mailing address block, addressee first; only the first five lines are printed.
+    /// Defaults to the customer name above the service address.
+    pub fn mailing_address(mut self, lines: &[&str]) -> Self {
+        self.mailing_address = Some(lines.iter().take(5).map(|l| l.to_string()).collect());
+        self
+    }
+
+    /// Set the bulletin message lines; text is padded or truncated to the field width
+    pub fn bulletin(mut self, lines: [&str; BULLETIN_LINES]) -> Self {
+        self.bulletin = lines.map(|l| l.to_string());
+        self
+    }
+
+    /// Add a line to the meter readings table
+    pub fn meter(mut self, reading: DuckMeterReading) -> Self {
+        self.meters.push(reading);
+        self
+    }
+
+    /// Set the previous balance in cents; negative for a credit
+    pub fn previous_balance(mut self, cents: i64) -> Self {
+        self.previous_balance = cents;
+        self
+    }
+
+    /// Set the total current charges in cents
+    pub fn current_charges(mut self, cents: i64) -> Self {
+        self.current_charges = cents;
+        self
+    }
+
+    /// Set the amount due by the due date in cents.
+    /// Defaults to the previous balance plus current charges.
+    pub fn amount_due(mut self, cents: i64) -> Self {
+        self.amount_due = Some(cents);
+        self
+    }
+
+    /// Set the amount due after the due date in cents.
+    /// Defaults to the amount due plus a 10% penalty on the smaller of
+    /// the amount due and the current charges.
+    pub fn amount_due_after(mut self, cents: i64) -> Self {
+        self.amount_due_after = Some(cents);
+        self
+    }
+
+    /// Set the barcode payload.
+    /// Defaults to the due year, account prefix, and zero-padded bill number.
+    pub fn barcode(mut self, payload: &str) -> Self {
+        self.barcode = Some(payload.to_string());
+        self
+    }
+
+    /// Produce the bill record. Bill numbers over seven digits can't be printed.
+    pub fn build(&self) -> DuckResult<DuckBill> {
+        if self.bill_number > MAX_BILL_NUMBER {
+            return Err(DuckError::BadBillNumberFormat);
+        }
+        DuckBill::new(self.render())
+    }
+
+    /// Lay out the raw PCL for this bill
+    fn render(&self) -> DuckData {
+        let acct = self.account_id.to_string();
+        let billnum = format!("{:07}", self.bill_number);
+        let due = self.amount_due.unwrap_or(self.previous_balance + self.current_charges);
+        let due_after = self.amount_due_after.unwrap_or_else(|| {
+            let penalty_base = due.min(self.current_charges).max(0);
+            due + (penalty_base + 5) / 10
+        });
+        let barcode = self.barcode.clone().unwrap_or_else(|| {
+            let year = self.due_date.get(6..8).unwrap_or("00");
+            format!("20{}{}{}", year, acct.get(0..2).unwrap_or("00"), billnum)
+        });
+        let service = |n: usize| self.service_address.get(n).cloned().unwrap_or_default();
+        let mailing = self.mailing_address.clone().unwrap_or_else(|| {
+            let mut m = vec![self.name.clone()];
+            m.extend(self.service_address.iter().cloned());
+            m.push("WATERTOWN NY  13601".to_string());
+            m
+        });
+        let pad = |text: &str, width: usize| format!("{:<width$.width$}", text, width = width);
+        let font = "\x1b(s1P\x1b(s5T\x1b(s10V";
+        let big = "\x1b(8U\x1b(s1p12v0s3b4101T";
+        let small = "\x1b(8U\x1b(s1p08v0s3b4101T";
+        let tiny = "\x1b(8U\x1b(s1p07v0s3b4101T";
+
+        let mut lines: Vec<String> = vec![
+            "\x1bE".to_string(),
+            "\x1b&l6D".to_string(),
+            format!("{font}\x1b&a0165v0240H\x1b(s3BBILL DATE \x1b&a0165v0825H{}", self.bill_date),
+            format!("\x1b&a0165v1520H{big}CITY OF WATERTOWN, NEW YORK"),
+            format!("{font}\x1b&a0165v4020HACCOUNT NO.\x1b&a0165v4800H{acct}"),
+            format!("\x1b&a0285v0240HDUE DATE   \x1b&a0285v0825H{}\x1b&a0285v1750H{big}WATER AND SEWER BILL     {font}", self.due_date),
+            "\x1b&a0525v3500HFOR SERVICE AT:".to_string(),
+            format!("\x1b&a0405v0240HBILL #:    \x1b&a0405v0825H{billnum}"),
+            format!("\x1b&a0405v2225H{}", self.phone),
+            format!("\x1b&a0645v3500H{}\x1b&a0765v3500H", pad(&service(0), TOP_SERVICE_ADDR_WIDTH)),
+            "\x1b&a0765v0240H             \x1b&a0765v0765H  \x1b&a0765v3500H\x1b(s0B".to_string(),
+            "\x1b&a0285v4020H".to_string(),
+            "\x1b&a0765v0960H\x1b(s3BMeter Readings    in CUBIC FT.           \x1b(s0B\x1b&a0405v4020H".to_string(),
+            "\x1b&a0885v0180HMtr\x1b&a0885v0780HPrevious\x1b&a0885v1920HPresent\x1b&a0885v2700HBill\x1b&a0885v3160HUsage\x1b&a0945v3720HWater".to_string(),
+            "\x1b&a0945v4380HSewer       \x1b&a0945v5040HTotal".to_string(),
+            "\x1b&a1005v0180HID\x1b&a1005v0520HRead Date \x1b&a1005v1080HReading\x1b&a1005v1620HRead Date \x1b&a1005v2160HReading\x1b&a1005v2700HCode                    \x1b(s0B".to_string(),
+            "\x1b(s0P\x1b(s3T\x1b(s12H".to_string(),
+        ];
+
+        for m in self.meters.iter() {
+            lines.push(format!("\n{}", m.to_line()));
+        }
+        for _ in self.meters.len()..METER_TABLE_ROWS {
+            lines.push("\n ".to_string());
+        }
+        for amount in [self.previous_balance, self.current_charges, due_after, due] {
+            lines.push(format!("\n{}", right_amount(amount)));
+        }
+        lines.push(font.to_string());
+
+        lines.extend([
+            format!("\x1b&a1845v0240H{font}{}\x1b&a1845v2590H\x1b(s3BPREVIOUS BALANCE    \x1b(s0B", pad(&self.bulletin[0], BULLETIN_WIDTH)),
+            format!("\x1b&a1965v0240H{}\x1b&a1965v2590H\x1b(s3BTOTAL CURRENT CHARGES\x1b(s0B", pad(&self.bulletin[1], BULLETIN_WIDTH)),
+            format!("\x1b&a2085v0240H{}\x1b&a2085v2590H\x1b(s3BAMT. DUE            {tiny}", pad(&self.bulletin[2], BULLETIN_WIDTH)),
+            format!("\x1b&a2085v3160HIF PAID AFTER \x1b&a2085v3730H{}\x1b&a2085v4000H{tiny}, includes 10% penalty   {font}\x1b(s0B", self.due_date),
+            format!("\x1b&a2205v0240H\x1b(s0B{}\x1b(s0B", pad(&self.bulletin[3], BULLETIN_WIDTH)),
+            format!("\x1b&a2205v2590H\x1b(s3BAMT. DUE IF PAID BY \x1b&a2205v3790H{}\x1b(s0B", self.due_date),
+            format!("\x1b&a2285v0240H{small}CODE: ACT-actual reading EST-estimated reading"),
+            format!("\x1b&a2285v3420HPlease keep this portion for your records{font}"),
+            format!("\x1b&a2625v3420H{small}Please include this portion with your payment{font}"),
+            format!("\x1b&a2805v1750H{big}          Water and Sewer Bill        {font}\x1b(s0B"),
+            format!("\x1b&a3045v0240HBill Date:       \x1b&a3045v0970H{}", self.bill_date),
+            format!("\x1b&a3165v0240HBill #:          \x1b&a3165v0970H{billnum}"),
+            format!("\x1b&a3285v0240HAccount #:       \x1b&a3285v0970H{acct}\x1b&a3285v3420H\x1b(s3BMake Checks Payable To:                \x1b(s0B"),
+            format!("\x1b&a3405v0240HService Address: \x1b&a3405v0970H{}", service(0)),
+            format!("\x1b&a3525v0970H{}", service(1)),
+            format!("\x1b&a3645v0240HName: \x1b&a3645v0970H{}", self.name),
+            "\x1b&a3645v3420H\x1b(s3BCITY COMPTROLLER                        \x1b(s0B".to_string(),
+            "\x1b&a3765v3420H245 WASHINGTON ST  STE 203".to_string(),
+            format!("\x1b&a3885v0240H{big}\x1b(s3B\x1b(s1SAmount Due if paid by \x1b&a3885v1600H{}:", self.due_date),
+            format!("\x1b&a3885v2240H{}\x1b(s0S\x1b(s0B{font}\x1b&a3885v3420H__CHECK HERE FOR A PAPER COPY OF", pad(&format_amount(due), STUB_AMOUNT_WIDTH)),
+            format!("\x1b&a4005v0240H{big}\x1b(s3B\x1b(s1SAmount Due if paid after\x1b&a4005v1600H{}:", self.due_date),
+            format!("\x1b&a4005v2240H{}\x1b(s0S\x1b(s0B{font}\x1b&a4005v3420HTHE 2020 ANNUAL WATER QUALITY", pad(&format_amount(due_after), STUB_AMOUNT_WIDTH)),
+            format!("\x1b&a4125v0240H{small}UNPAID BALANCES WILL BE ADDED TO THE 2021-22 CITY TAX 6/15/2021       {font}"),
+            "\x1b&a4125v3420HREPORT OR VIEW ONLINE AT:".to_string(),
+            format!("\x1b&a4245v0240H{small}{}{font}", " ".repeat(70)),
+            "\x1b&a4245v3420Hhttp://www.watertown-ny.gov/awqr".to_string(),
+            "\x1b&a5445v0240HCITY OF WATERTOWN WATER DEPT.".to_string(),
+            "\x1b&a5565v0240H245 WASHINGTON STREET, SUITE 202".to_string(),
+            "\x1b&a5685v0240HWATERTOWN, NEW YORK 13601-3384".to_string(),
+            "\x1b&a5805v0240H-".to_string(),
+            "\x1b&a5925v0240HADDRESS SERVICE REQUESTED".to_string(),
+            format!("\x1b&a6045v0240HAcct No: {acct}"),
+        ]);
+
+        for (n, line) in mailing.iter().enumerate() {
+            lines.push(format!("\x1b&a{:04}v2220H{}", 6455 + 120 * n, line));
+        }
+
+        for rule in RULES {
+            lines.push(rule.to_string());
+        }
+        for v in ["4440", "4560"] {
+            lines.push(format!("\x1b&a{v}v0540H\x1b(0Y\x1b(s0p8.1h18v0s0b0T*{barcode}*"));
+        }
+        lines.push(String::new());
+
+        DuckData::from(lines.join("\r").as_str())
+    }
+}
+
+/// Right-align an amount with the meter table, credits hanging one column past it
+fn right_amount(cents: i64) -> String {
+    let text = format_amount(cents);
+    let width = if cents < 0 { METER_TABLE_WIDTH + 1 } else { METER_TABLE_WIDTH };
+    format!("{:>width$}", text, width = width)
+}
+
+/// The boxes and rules drawn on every bill
+const RULES: [&str; 28] = [
+    "\x1b&a0660v0120H\x1b*c3420h0006v0P", "\x1b&a0780v0120H\x1b*c5400h0006v0P",
+    "\x1b&a0900v0390H\x1b*c2270h0006v0P", "\x1b&a1020v0120H\x1b*c5400h0006v0P",
+    "\x1b&a1140v0120H\x1b*c5400h0006v0P", "\x1b&a1260v0120H\x1b*c5400h0006v0P",
+    "\x1b&a1380v0120H\x1b*c5400h0006v0P", "\x1b&a1500v0120H\x1b*c5400h0006v0P",
+    "\x1b&a1620v0120H\x1b*c5400h0006v0P", "\x1b&a1740v0120H\x1b*c5400h0006v0P",
+    "\x1b&a1860v2500H\x1b*c3030h0006v0P", "\x1b&a1980v2500H\x1b*c3030h0006v0P",
+    "\x1b&a2100v2500H\x1b*c3030h0006v0P", "\x1b&a2220v2500H\x1b*c3030h0006v0P",
+    "\x1b&a0660v0120H\x1b*c0006h1080v0P", "\x1b&a0780v0390H\x1b*c0006h0960v0P",
+    "\x1b&a0900v0920H\x1b*c0006h0840v0P", "\x1b&a0780v1520H\x1b*c0006h0960v0P",
+    "\x1b&a0900v2080H\x1b*c0006h0840v0P", "\x1b&a0780v2660H\x1b*c0006h0960v0P",
+    "\x1b&a0780v2930H\x1b*c0006h0960v0P", "\x1b&a0660v3540H\x1b*c0006h1080v0P",
+    "\x1b&a0780v4200H\x1b*c0006h0960v0P", "\x1b&a0780v4860H\x1b*c0006h0960v0P",
+    "\x1b&a0780v5520H\x1b*c0006h0960v0P", "\x1b&a1740v2500H\x1b*c0006h0480v0P",
+    "\x1b&a1740v4800H\x1b*c0006h0480v0P", "\x1b&a1740v5520H\x1b*c0006h0480v0P",
+];
+
+/// A small, fast, seedable pseudo-random generator (SplitMix64).
+/// Good enough for varied test data; not for anything secret.
+pub struct DuckRng {
+    state: u64,
+}
+
+impl DuckRng {
+    pub fn new(seed: u64) -> DuckRng {
+        DuckRng { state: seed }
+    }
+
+    /// Get the next pseudo-random value
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a pseudo-random value in 0..bound
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+}
+
+/// Generate `count` varied but valid bills. The same seed always gives the same bills.
+/// Account IDs and bill numbers are unique within the result.
+pub fn synthetic_bills(count: usize, seed: u64) -> DuckResult<Vec<DuckBill>> {
+    let mut rng = DuckRng::new(seed);
+    let mut bills = Vec::with_capacity(count);
+
+    for n in 0..count {
+        let acct = format!("{:02}-{:07}-{}", 10 + rng.below(90), n, rng.below(10));
+        let account_id = DuckAcctId::try_from(acct.into_bytes())?;
+        let name = format!("{} {}",
+                           FAKE_FIRST_NAMES[rng.below(8) as usize],
+                           FAKE_LAST_NAMES[rng.below(8) as usize]);
+        let street = format!("{} {} ST", 1 + rng.below(999), FAKE_STREETS[rng.below(8) as usize]);
+        let town = format!("{}  {:05}", FAKE_TOWNS[rng.below(4) as usize], rng.below(100_000));
+
+        let mut builder = DuckBillBuilder::new(account_id, 1 + n as u32)
+            .name(&name)
+            .service_address(&[&street])
+            .mailing_address(&[&name, &street, &town])
+            .previous_balance(rng.below(20_000) as i64 - 5_000);
+
+        let mut current = 0;
+        for m in 0..=rng.below(2) {
+            let previous = rng.below(100_000);
+            let usage = rng.below(50);
+            let water = 1_000 + rng.below(10_000) as i64;
+            let sewer = rng.below(10_000) as i64;
+            current += water + sewer;
+            builder = builder.meter(DuckMeterReading {
+                meter_id: format!("{:03}", m + 1),
+                previous_date: "1/04/21".to_string(),
+                previous_reading: previous,
+                present_date: "4/01/21".to_string(),
+                present_reading: previous + usage,
+                code: if rng.below(5) == 0 { "EST." } else { "ACT." }.to_string(),
+                usage,
+                water: Some(water),
+                sewer: if sewer == 0 { None } else { Some(sewer) },
+                total: water + sewer,
+            });
+        }
+
+        bills.push(builder.current_charges(current).build()?);
+    }
+
+    Ok(bills)
+}
+
+/// Generate a whole bill file of `count` synthetic bills
+pub fn synthetic_file(count: usize, seed: u64) -> DuckResult<DuckFile> {
+    DuckFile::try_from(synthetic_bills(count, seed)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::tests::get_test_data;
+
+    /// Rebuild the first bill of the test data from its typed values
+    fn first_test_bill() -> DuckBillBuilder {
+        DuckBillBuilder::new(DuckAcctId::normalize("52-1111111-1").unwrap(), 7488)
+            .name("NAME NUMBER 11")
+            .service_address(&["123 BILLONE ST"])
+            .mailing_address(&["NAME NUMBER 11", "111 ADDR LINE 1ST", "AMHERST MA  01002"])
+            .meter(DuckMeterReading {
+                meter_id: "001".to_string(),
+                previous_date: "1/04/21".to_string(),
+                previous_reading: 802,
+                present_date: "4/01/21".to_string(),
+                present_reading: 808,
+                code: "ACT.".to_string(),
+                usage: 6,
+                water: Some(3683),
+                sewer: Some(3232),
+                total: 6915,
+            })
+            .previous_balance(7606)
+            .current_charges(6915)
+            .amount_due_after(15212)
+    }
+
+    #[test]
+    fn builder_reproduces_real_bill() {
+        let quack = get_test_data();
+        let built = first_test_bill().build().unwrap();
+
+        assert_eq!(built.get_raw().to_string(), quack[0].get_raw().to_string());
+        assert_eq!(built, quack[0]);
+    }
+
+    #[test]
+    fn built_bills_parse_into_file() {
+        let bills = vec![
+            first_test_bill().build().unwrap(),
+            DuckBillBuilder::new(DuckAcctId::normalize("0100000010").unwrap(), 1)
+                .previous_balance(-4952)
+                .current_charges(5188)
+                .build()
+                .unwrap(),
+        ];
+        let file = DuckFile::try_from(bills).unwrap();
+
+        assert_eq!(file.get_bill_count(), 2);
+        assert_eq!(file[1].get_bill_number(), 1);
+        assert_eq!(file[1].get_name(), Some("CUSTOMER".to_string()));
+    }
+
+    #[test]
+    fn bill_numbers_fit_seven_digits() {
+        let acct = DuckAcctId::normalize("0100000010").unwrap();
+        assert_eq!(DuckBillBuilder::new(acct.clone(), 9_999_999).build().unwrap().get_bill_number(), 9_999_999);
+        assert_eq!(DuckBillBuilder::new(acct, 10_000_000).build(), Err(DuckError::BadBillNumberFormat));
+    }
+
+    #[test]
+    fn synthetic_files_are_deterministic() {
+        let a = synthetic_file(50, 42).unwrap();
+        let b = synthetic_file(50, 42).unwrap();
+        let c = synthetic_file(50, 43).unwrap();
+
+        assert_eq!(a.get_bill_count(), 50);
+        assert_eq!(a[..], b[..]);
+        assert_ne!(a[..], c[..]);
+        for (n, bill) in a[..].iter().enumerate() {
+            assert_eq!(a.get_index_of_account(bill.get_account_id()), Some(n));
+        }
+    }
+
+    #[test]
+    fn rng_is_repeatable() {
+        let mut a = DuckRng::new(7);
+        let mut b = DuckRng::new(7);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert!(DuckRng::new(7).below(10) < 10);
+    }
+}
@@ -0,0 +1,180 @@
+//! The bulletin is the free-text message block printed on every bill,
+//! e.g. "PAYMENTS MAY BE DROPPED OF IN / SILVER LOCKBOX ON STERLING ST".
+//! Replacing it in place avoids re-running a whole billing cycle for a last-minute change.
+use std::ops::Range;
+use super::DuckFile;
+use super::duckbill::DuckBill;
+use super::duckpcl::{escape_len, CR, ESC};
+
+/// Cursor positions of the bulletin lines, top to bottom
+pub const BULLETIN_POS_BYTES: [&[u8]; 4] = [
+    b"\x1b&a1845v0240H", b"\x1b&a1965v0240H", b"\x1b&a2085v0240H", b"\x1b&a2205v0240H",
+];
+
+/// The outcome of replacing the bulletin across a bill file
+#[derive(Debug, PartialEq, Default)]
+pub struct DuckBulletinReport {
+    replaced: usize,
+    missing: Vec<usize>,
+    truncated: Vec<usize>,
+}
+
+impl DuckBulletinReport {
+    /// Get the number of bills whose bulletin was replaced
+    pub fn get_replaced_count(&self) -> usize {
+        self.replaced
+    }
+
+    /// Get the indices of bills where the bulletin lines weren't found; these are left unchanged
+    pub fn get_missing(&self) -> &[usize] {
+        &self.missing
+    }
+
+    /// Get the numbers (0-based) of lines that were too long for their field on at least one bill
+    pub fn get_truncated_lines(&self) -> &[usize] {
+        &self.truncated
+    }
+}
+
+/// Find the printed text of each bulletin line.
+/// Font changes between the cursor position and the text are skipped,
+/// and each range includes the line's padding, which is the field width.
+fn bulletin_ranges(bill: &DuckBill) -> Option<Vec<Range<usize>>> {
+    let raw = bill.get_raw().as_ref();
+    BULLETIN_POS_BYTES.iter()
+        .map(|marker| {
+            let mut start = raw.windows(marker.len()).position(|w| w == *marker)? + marker.len();
+            while raw.get(start) == Some(&ESC) {
+                start += escape_len(raw, start);
+            }
+            let len = raw[start..].iter().position(|&b| b == ESC || b == CR).unwrap_or(raw.len() - start);
+            Some(start..start + len)
+        })
+        .collect()
+}
+
+impl DuckBill {
+    /// Get the bulletin lines with padding removed, or None if this bill has no bulletin
+    pub fn get_bulletin(&self) -> Option<Vec<String>> {
+        let raw = self.get_raw().as_ref();
+        bulletin_ranges(self).map(|ranges| {
+            ranges.into_iter()
+                .map(|r| String::from_utf8_lossy(&raw[r]).trim_end().to_string())
+                .collect()
+        })
+    }
+
+    /// Get the width of each bulletin line's field, or None if this bill has no bulletin
+    pub fn get_bulletin_widths(&self) -> Option<Vec<usize>> {
+        bulletin_ranges(self).map(|ranges| ranges.iter().map(|r| r.len()).collect())
+    }
+
+    /// Replace the bulletin text. Each line is padded with spaces or truncated
+    /// to its field's width, so nothing else on the bill moves.
+    /// Missing lines are left blank and extra lines are ignored.
+    /// Returns the numbers of lines that had to be truncated,
+    /// or None (leaving the bill unchanged) if the bulletin wasn't found.
+    pub fn replace_bulletin(&mut self, lines: &[&str]) -> Option<Vec<usize>> {
+        let ranges = bulletin_ranges(self)?;
+        let raw = self.get_raw_mut();
+        let mut truncated = vec![];
+
+        for (n, range) in ranges.into_iter().enumerate() {
+            let text = lines.get(n).copied().unwrap_or("").as_bytes();
+            let text = &text[..text.iter().position(|&b| b < 0x20).unwrap_or(text.len())];
+            if text.len() > range.len() {
+                truncated.push(n);
+            }
+            let field = &mut raw[range];
+            field.fill(b' ');
+            let keep = text.len().min(field.len());
+            field[..keep].copy_from_slice(&text[..keep]);
+        }
+
+        Some(truncated)
+    }
+}
+
+impl DuckFile {
+    /// Replace the bulletin on every bill. See DuckBill::replace_bulletin.
+    pub fn replace_bulletin(&mut self, lines: &[&str]) -> DuckBulletinReport {
+        let mut report = DuckBulletinReport::default();
+
+        for (index, bill) in self.bills.iter_mut().enumerate() {
+            match bill.replace_bulletin(lines) {
+                Some(truncated) => {
+                    report.replaced += 1;
+                    for n in truncated {
+                        if !report.truncated.contains(&n) {
+                            report.truncated.push(n);
+                        }
+                    }
+                }
+                None => report.missing.push(index),
+            }
+        }
+
+        report.truncated.sort();
+        report
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::duckdata::DuckData;
+    use crate::duckfile::tests::get_test_data;
+
+    #[test]
+    fn bulletin_found() {
+        let quack = get_test_data();
+        assert_eq!(quack[0].get_bulletin().unwrap(), vec![
+            "PAYMENTS MAY BE DROPPED OF IN",
+            "SILVER LOCKBOX ON STERLING ST",
+            "WATER FLUSHING WILL BEGIN",
+            "MAY 9, 2021",
+        ]);
+        assert_eq!(quack[0].get_bulletin_widths().unwrap(), vec![48, 48, 48, 48]);
+    }
+
+    #[test]
+    fn replacement_keeps_width() {
+        let mut quack = get_test_data();
+        let before: Vec<usize> = quack[..].iter().map(|b| b.get_raw().len()).collect();
+
+        let long = "X".repeat(60);
+        let report = quack.replace_bulletin(&["OFFICE CLOSED MONDAY", &long]);
+        assert_eq!(report.get_replaced_count(), 4);
+        assert!(report.get_missing().is_empty());
+        assert_eq!(report.get_truncated_lines(), &[1]);
+
+        for (n, bill) in quack[..].iter().enumerate() {
+            assert_eq!(bill.get_raw().len(), before[n]);
+            assert_eq!(bill.get_bulletin().unwrap(), vec!["OFFICE CLOSED MONDAY", &long[..48], "", ""]);
+        }
+
+        // the result is still a valid bill file
+        let data: DuckData = quack.into();
+        let reparsed = DuckFile::try_from(data).unwrap();
+        assert_eq!(reparsed[3].get_bulletin().unwrap()[0], "OFFICE CLOSED MONDAY");
+    }
+
+    #[test]
+    fn bills_without_bulletin_reported() {
+        let quack = get_test_data();
+        let mut bills = quack[..].to_vec();
+
+        // knock out one bulletin cursor position on the second bill
+        let raw = bills[1].get_raw_mut();
+        let at = raw.windows(BULLETIN_POS_BYTES[2].len()).position(|w| w == BULLETIN_POS_BYTES[2]).unwrap();
+        raw[at + 3] = b'9';
+        let untouched = bills[1].clone();
+
+        let mut quack = DuckFile::try_from(bills).unwrap();
+        let report = quack.replace_bulletin(&["NEW"]);
+        assert_eq!(report.get_missing(), &[1]);
+        assert_eq!(report.get_replaced_count(), 3);
+        assert_eq!(quack[1], untouched);
+    }
+}
@@ -0,0 +1,196 @@
+//! Compressed bill files. Archived runs are usually gzipped and vendors sometimes send zips,
+//! so input is recognised by its magic bytes and decompressed before parsing.
+//! Decompression stops just past MAX_FILE_LEN, so a small archive that expands enormously
+//! (a "zip bomb") is refused with FileTooBig instead of filling memory.
+use std::io::Read;
+#[cfg(feature = "compression")]
+use std::io::{Cursor, Write};
+use super::MAX_FILE_LEN;
+use super::duckerror::DuckError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// How a bill file is compressed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DuckCompression {
+    None,
+    Gzip,
+    /// A zip archive holding just the bill file
+    Zip,
+}
+
+impl DuckCompression {
+    /// Recognise compressed data by its first bytes
+    pub fn detect(data: &[u8]) -> DuckCompression {
+        if data.starts_with(GZIP_MAGIC) {
+            DuckCompression::Gzip
+        } else if data.starts_with(ZIP_MAGIC) {
+            DuckCompression::Zip
+        } else {
+            DuckCompression::None
+        }
+    }
+
+    /// Choose compression from a file name's extension: .gz for gzip, .zip for zip
+    pub fn from_file_name(name: &str) -> DuckCompression {
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".gz") {
+            DuckCompression::Gzip
+        } else if name.ends_with(".zip") {
+            DuckCompression::Zip
+        } else {
+            DuckCompression::None
+        }
+    }
+}
+
+/// Read at most MAX_FILE_LEN bytes, failing with FileTooBig if there's more
+pub(crate) fn read_limited<R: Read>(reader: R) -> Result<Vec<u8>, DuckError> {
+    read_within(reader, MAX_FILE_LEN)
+}
+
+/// Read at most `limit` bytes, failing with FileTooBig if there's more
+fn read_within<R: Read>(reader: R, limit: usize) -> Result<Vec<u8>, DuckError> {
+    let mut data = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut data)?;
+    if data.len() > limit {
+        return Err(DuckError::FileTooBig);
+    }
+    Ok(data)
+}
+
+/// Decompress data if it's gzip or zip, or return it unchanged
+#[cfg(feature = "compression")]
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, DuckError> {
+    decompress_within(data, MAX_FILE_LEN)
+}
+
+/// Decompress data to at most `limit` bytes; tests use a small limit to keep their bombs small
+#[cfg(feature = "compression")]
+fn decompress_within(data: Vec<u8>, limit: usize) -> Result<Vec<u8>, DuckError> {
+    match DuckCompression::detect(&data) {
+        DuckCompression::None => Ok(data),
+        DuckCompression::Gzip => read_within(flate2::read::MultiGzDecoder::new(&data[..]), limit),
+        DuckCompression::Zip => {
+            let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|_| DuckError::BadData)?;
+            let files: Vec<usize> = (0..archive.len())
+                .filter(|&i| archive.by_index_raw(i).is_ok_and(|f| f.is_file()))
+                .collect();
+            if files.len() != 1 {
+                return Err(DuckError::BadData);
+            }
+            let entry = archive.by_index(files[0]).map_err(|_| DuckError::BadData)?;
+            read_within(entry, limit)
+        }
+    }
+}
+
+/// Compressed input can't be read without the compression feature
+#[cfg(not(feature = "compression"))]
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, DuckError> {
+    match DuckCompression::detect(&data) {
+        DuckCompression::None => Ok(data),
+        _ => Err(DuckError::BadData),
+    }
+}
+
+/// Compress data; a zip archive gets a single entry with the given name
+#[cfg(feature = "compression")]
+pub fn compress(data: &[u8], compression: DuckCompression, entry_name: &str) -> Result<Vec<u8>, DuckError> {
+    match compression {
+        DuckCompression::None => Ok(data.to_vec()),
+        DuckCompression::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        DuckCompression::Zip => {
+            let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(data.len() > u32::MAX as usize);
+            writer.start_file(entry_name, options).map_err(|_| DuckError::BadData)?;
+            writer.write_all(data)?;
+            Ok(writer.finish().map_err(|_| DuckError::BadData)?.into_inner())
+        }
+    }
+}
+
+/// Output can't be compressed without the compression feature
+#[cfg(not(feature = "compression"))]
+pub fn compress(data: &[u8], compression: DuckCompression, _entry_name: &str) -> Result<Vec<u8>, DuckError> {
+    match compression {
+        DuckCompression::None => Ok(data.to_vec()),
+        _ => Err(DuckError::BadData),
+    }
+}
+
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use crate::duckfile::DuckFile;
+    use crate::duckfile::duckdata::DuckData;
+    use crate::duckfile::tests::get_test_data;
+
+    fn plain() -> Vec<u8> {
+        let data: DuckData = get_test_data().into();
+        data.as_ref().to_vec()
+    }
+
+    #[test]
+    fn detects_by_magic() {
+        assert_eq!(DuckCompression::detect(&plain()), DuckCompression::None);
+        assert_eq!(DuckCompression::detect(&compress(&plain(), DuckCompression::Gzip, "").unwrap()), DuckCompression::Gzip);
+        assert_eq!(DuckCompression::detect(&compress(&plain(), DuckCompression::Zip, "a.dat").unwrap()), DuckCompression::Zip);
+        assert_eq!(DuckCompression::from_file_name("run.DUCKED.GZ"), DuckCompression::Gzip);
+        assert_eq!(DuckCompression::from_file_name("run.zip"), DuckCompression::Zip);
+        assert_eq!(DuckCompression::from_file_name("run.zip.DUCKED"), DuckCompression::None);
+    }
+
+    #[test]
+    fn round_trips() {
+        for compression in [DuckCompression::None, DuckCompression::Gzip, DuckCompression::Zip] {
+            let packed = compress(&plain(), compression, "run.dat").unwrap();
+            assert_eq!(decompress(packed.clone()).unwrap(), plain());
+            assert_eq!(DuckFile::read_from(&packed[..]).unwrap().get_bill_count(), 4);
+        }
+    }
+
+    #[test]
+    fn zips_need_exactly_one_file() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("one.dat", options).unwrap();
+        writer.write_all(&plain()).unwrap();
+        writer.start_file("two.dat", options).unwrap();
+        writer.write_all(&plain()).unwrap();
+        let two = writer.finish().unwrap().into_inner();
+        assert_eq!(decompress(two), Err(DuckError::BadData));
+
+        let empty = zip::ZipWriter::new(Cursor::new(Vec::new())).finish().unwrap().into_inner();
+        assert!(DuckFile::read_from(&empty[..]).is_err());
+    }
+
+    #[test]
+    fn bombs_are_too_big() {
+        // a few kilobytes that expand just past a one megabyte limit
+        const LIMIT: usize = 1 << 20;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        std::io::copy(&mut std::io::repeat(b'Q').take(LIMIT as u64 + 1), &mut encoder).unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < LIMIT / 100);
+        assert_eq!(decompress_within(bomb, LIMIT), Err(DuckError::FileTooBig));
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("bomb.dat", zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::copy(&mut std::io::repeat(b'Q').take(LIMIT as u64 + 1), &mut writer).unwrap();
+        let bomb = writer.finish().unwrap().into_inner();
+        assert_eq!(decompress_within(bomb, LIMIT), Err(DuckError::FileTooBig));
+
+        // exactly at the limit is fine
+        let fits = compress(&vec![b'Q'; LIMIT], DuckCompression::Gzip, "").unwrap();
+        assert_eq!(decompress_within(fits, LIMIT).map(|d| d.len()), Ok(LIMIT));
+    }
+}
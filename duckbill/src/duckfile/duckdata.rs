@@ -1,4 +1,5 @@
 //!DuckData is a thin wrapper around Vec<u8> to allow for type checking and minor features.
+use std::fmt::{Display, Formatter};
 use std::ops::{Index};
 use std::slice::SliceIndex;
 
@@ -37,15 +38,16 @@ impl DuckData {
     }
 }
 
-impl ToString for DuckData {
-    fn to_string(&self) -> String {
-        String::from_utf8(self.my_data.clone()).unwrap_or_else(|_| {
+impl Display for DuckData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = String::from_utf8(self.my_data.clone()).unwrap_or_else(|_| {
             self.my_data
                 .iter()
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>()
                 .join(" ")
-        })
+        });
+        write!(f, "{}", s)
     }
 }
 
@@ -91,10 +93,6 @@ impl PartialEq<&[u8]> for DuckData {
     fn eq(&self, other: &&[u8]) -> bool {
         self.my_data == *other
     }
-
-    fn ne(&self, other: &&[u8]) -> bool {
-        !(self.my_data == *other)
-    }
 }
 
 impl<I> Index<I> for DuckData
@@ -113,6 +111,12 @@ impl AsRef<[u8]> for DuckData {
     }
 }
 
+impl AsMut<[u8]> for DuckData {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.my_data
+    }
+}
+
 
 
 #[cfg(test)]
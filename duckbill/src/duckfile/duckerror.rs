@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use std::num::ParseIntError;
 use std::string::FromUtf8Error;
 
@@ -27,6 +28,39 @@ pub enum DuckError {
     FileTooBig,
 }
 
+/// a short description fit to show a person
+impl Display for DuckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            DuckError::BadData => "the data isn't in the expected format",
+            DuckError::NotEnoughMarkers => "not enough bill markers were found",
+            DuckError::DataTooShort => "the data is too short",
+            DuckError::LengthMarkerMissing => "a length marker is missing",
+            DuckError::LengthMarkerFormatBad => "a length marker is badly formatted",
+            DuckError::MarkCountMismatch => "the number of bills doesn't match the count",
+            DuckError::BadStringData => "the text isn't valid",
+            DuckError::BadNumberData => "that isn't a valid number",
+            DuckError::NoAccountIDFound => "no account ID was found",
+            DuckError::BadAccountIdFormat => "account IDs look like 01-0123456-0",
+            DuckError::AccountIDTooShort => "that account ID is too short; account IDs look like 01-0123456-0",
+            DuckError::BadHeaderFormat => "the file header is badly formatted",
+            DuckError::HeaderTooShort => "the file header is too short",
+            DuckError::BadFooterFormat => "the file footer is badly formatted",
+            DuckError::BillCountOutOfBounds => "the bill count is out of range",
+            DuckError::BadIdentifierData => "an identifier isn't valid",
+            DuckError::BadBillNumberFormat => "a bill number is badly formatted",
+            DuckError::NegativeNumber => "the number can't be negative",
+            DuckError::OpCancelled => "cancelled",
+            DuckError::IoError => "the file couldn't be read or written",
+            DuckError::FileTooSmall => "the file is too short to be a bill file",
+            DuckError::FileTooBig => "the file is too large to be a bill file",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for DuckError {}
+
 impl From<std::string::FromUtf8Error> for DuckError {
     fn from(_: FromUtf8Error) -> Self {
         DuckError::BadStringData
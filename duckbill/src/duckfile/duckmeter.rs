@@ -0,0 +1,141 @@
+//! DuckMeterReading is one line of the meter readings table printed on each bill.
+
+/// A single meter's readings and charges for the billing period.
+/// Money amounts are in cents.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuckMeterReading {
+    pub meter_id: String,
+    pub previous_date: String,
+    pub previous_reading: u64,
+    pub present_date: String,
+    pub present_reading: u64,
+    pub code: String,
+    pub usage: u64,
+    pub water: Option<i64>,
+    pub sewer: Option<i64>,
+    pub total: i64,
+}
+
+impl DuckMeterReading {
+    /// Render the reading as it appears in the fixed-width meter table
+    pub fn to_line(&self) -> String {
+        let charge = |c: Option<i64>| c.map(format_amount).unwrap_or_default();
+        format!("{:>6}{:>9}{:>10}{:>9}{:>10} {:<4}{:>9}{:>11}{:>11}{:>11}",
+                self.meter_id,
+                self.previous_date,
+                self.previous_reading,
+                self.present_date,
+                self.present_reading,
+                self.code,
+                self.usage,
+                charge(self.water),
+                charge(self.sewer),
+                format_amount(self.total))
+    }
+
+    /// Read a line of the meter table back into a reading.
+    /// Returns None for lines that aren't readings, such as the table's blank rows.
+    pub fn from_line(line: &str) -> Option<DuckMeterReading> {
+        // the columns of to_line
+        let column = |from: usize, to: usize| line.get(from..to.min(line.len())).map(str::trim);
+        let number = |from: usize, to: usize| column(from, to)?.parse::<u64>().ok();
+        let charge = |from: usize, to: usize| match column(from, to)? {
+            "" => Some(None),
+            text => parse_amount(text).map(Some),
+        };
+        if line.len() < 81 || !line.is_ascii() {
+            return None;
+        }
+        let reading = DuckMeterReading {
+            meter_id: column(0, 6)?.to_string(),
+            previous_date: column(6, 15)?.to_string(),
+            previous_reading: number(15, 25)?,
+            present_date: column(25, 34)?.to_string(),
+            present_reading: number(34, 44)?,
+            code: column(45, 49)?.to_string(),
+            usage: number(49, 58)?,
+            water: charge(58, 69)?,
+            sewer: charge(69, 80)?,
+            total: parse_amount(column(80, line.len())?)?,
+        };
+        (!reading.meter_id.is_empty()).then_some(reading)
+    }
+}
+
+/// Format cents the way bills print money: $12.34, with a trailing minus for credits
+pub fn format_amount(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("${}.{:02}{}", cents / 100, cents % 100, sign)
+}
+
+/// Read money the way bills print it back into cents; the opposite of format_amount.
+/// Amounts too large to hold in cents give None.
+pub fn parse_amount(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (text, credit) = match text.strip_suffix('-') {
+        Some(credit) => (credit, true),
+        None => (text, false),
+    };
+    let (dollars, cents) = text.strip_prefix('$')?.split_once('.')?;
+    if cents.len() != 2 || !cents.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let dollars: i64 = dollars.replace(',', "").parse().ok()?;
+    let cents: i64 = cents.parse().ok()?;
+    let amount = dollars.checked_mul(100)?.checked_add(cents)?;
+    if credit { amount.checked_neg() } else { Some(amount) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_format_like_bills() {
+        assert_eq!(format_amount(14521), "$145.21");
+        assert_eq!(format_amount(5), "$0.05");
+        assert_eq!(format_amount(-4952), "$49.52-");
+        for cents in [14521, 5, -4952, 0] {
+            assert_eq!(parse_amount(&format_amount(cents)), Some(cents));
+        }
+        assert_eq!(parse_amount("$1,234.50 "), Some(123450));
+        assert_eq!(parse_amount("12.34"), None);
+        assert_eq!(parse_amount("$12.3"), None);
+        assert_eq!(parse_amount("$12.-3"), None);
+    }
+
+    #[test]
+    fn huge_amounts_are_refused() {
+        assert_eq!(parse_amount("$99999999999999999.00"), None);
+        assert_eq!(parse_amount("$92233720368547758.08"), None);
+        assert_eq!(parse_amount("$92233720368547758.07"), Some(i64::MAX));
+        assert_eq!(parse_amount("$92233720368547758.07-"), Some(-i64::MAX));
+        assert_eq!(format_amount(i64::MIN), "$92233720368547758.08-");
+    }
+
+    #[test]
+    fn line_matches_printed_layout() {
+        let reading = DuckMeterReading {
+            meter_id: "001".to_string(),
+            previous_date: "1/04/21".to_string(),
+            previous_reading: 802,
+            present_date: "4/01/21".to_string(),
+            present_reading: 808,
+            code: "ACT.".to_string(),
+            usage: 6,
+            water: Some(3683),
+            sewer: Some(3232),
+            total: 6915,
+        };
+        assert_eq!(reading.to_line(),
+                   "   001  1/04/21       802  4/01/21       808 ACT.        6     $36.83     $32.32     $69.15");
+        assert_eq!(DuckMeterReading::from_line(&reading.to_line()), Some(reading.clone()));
+
+        let credit = DuckMeterReading { water: None, sewer: None, total: -4952, ..reading };
+        assert_eq!(DuckMeterReading::from_line(&credit.to_line()), Some(credit));
+        assert_eq!(DuckMeterReading::from_line(" "), None);
+        assert_eq!(DuckMeterReading::from_line(&format!("{:>91}", "$76.06")), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Stamp extra text, such as "DUPLICATE" or "REPRINT", onto bills.
+//! The stamp is added after everything else on each page of the bill, just before the
+//! page is ejected, with the cursor position saved and restored around it. Its font is
+//! set up as the secondary font and shifted in and out with SO and SI, so the bill's own
+//! (primary) font is still selected for anything printed after it.
+use std::fmt::{Display, Formatter};
+use super::DuckFile;
+use super::duckbill::{DuckBill, DuckResult};
+use super::duckdata::DuckData;
+use super::duckerror::DuckError;
+
+/// Default stamp position: the blank band above the payment stub, in decipoints
+const DEFAULT_V: u32 = 2500;
+const DEFAULT_H: u32 = 240;
+/// Default stamp size in points
+const DEFAULT_POINTS: u32 = 18;
+
+/// Text to print over a bill, with its position, font size, weight and rotation
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuckOverlay {
+    text: String,
+    v: u32,
+    h: u32,
+    points: u32,
+    bold: bool,
+    rotation: u16,
+}
+
+impl DuckOverlay {
+    /// Make a stamp with custom text at the default position and size.
+    /// Control characters are removed from the text, and anything outside ASCII,
+    /// which the stamp's symbol set can't be trusted to print, becomes '?'.
+    pub fn custom(text: &str) -> DuckOverlay {
+        DuckOverlay {
+            text: text.chars().filter(|c| !c.is_control()).map(|c| if c.is_ascii() { c } else { '?' }).collect(),
+            v: DEFAULT_V,
+            h: DEFAULT_H,
+            points: DEFAULT_POINTS,
+            bold: true,
+            rotation: 0,
+        }
+    }
+
+    /// The "DUPLICATE" stamp
+    pub fn duplicate() -> DuckOverlay {
+        DuckOverlay::custom("DUPLICATE")
+    }
+
+    /// The "REPRINT <date>" stamp
+    pub fn reprint(date: &str) -> DuckOverlay {
+        DuckOverlay::custom(&format!("REPRINT {}", date))
+    }
+
+    /// Move the stamp; positions are in decipoints (1/720 inch) from the top left
+    pub fn at(mut self, v: u32, h: u32) -> DuckOverlay {
+        self.v = v.min(9999);
+        self.h = h.min(9999);
+        self
+    }
+
+    /// Set the font size in points (1 to 999)
+    pub fn size(mut self, points: u32) -> DuckOverlay {
+        self.points = points.clamp(1, 999);
+        self
+    }
+
+    /// Set whether the stamp is printed bold
+    pub fn bold(mut self, bold: bool) -> DuckOverlay {
+        self.bold = bold;
+        self
+    }
+
+    /// Set the rotation in degrees counterclockwise; PCL allows only 0, 90, 180 or 270
+    pub fn rotation(mut self, degrees: u16) -> Result<DuckOverlay, DuckError> {
+        if !degrees.is_multiple_of(90) || degrees >= 360 {
+            return Err(DuckError::BadData);
+        }
+        self.rotation = degrees;
+        Ok(self)
+    }
+
+    /// Get the stamp text
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Produce the PCL that prints this stamp
+    pub fn to_pcl(&self) -> DuckData {
+        let pcl = format!(
+            "\x1b&f0S\x1b&a{}P\x1b&a{:04}v{:04}H\x1b)8U\x1b)s1p{}v0s{}b4101T\x0e{}\x0f\x1b&a0P\x1b&f1S",
+            self.rotation, self.v, self.h, self.points, if self.bold { 3 } else { 0 }, self.text
+        );
+        DuckData::from(pcl.as_str())
+    }
+}
+
+impl Display for DuckOverlay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl DuckBill {
+    /// Make a copy of this bill with a stamp printed over every page
+    pub fn with_overlay(&self, overlay: &DuckOverlay) -> DuckResult<DuckBill> {
+        let raw = self.get_raw();
+        let mut ends = self.page_ends();
+        if ends.is_empty() {
+            ends.push(raw.len());
+        }
+        let mut data = DuckData::new(vec![]);
+        let mut from = 0;
+        for end in ends {
+            data.push(DuckData::from(&raw[from..end]));
+            data.push(overlay.to_pcl());
+            from = end;
+        }
+        data.push(DuckData::from(&raw[from..]));
+        DuckBill::new(data)
+    }
+}
+
+impl DuckFile {
+    /// Make a copy of this file with a stamp printed over every bill
+    pub fn with_overlay(&self, overlay: &DuckOverlay) -> DuckResult<DuckFile> {
+        let bills = self.bills.iter()
+            .map(|b| b.with_overlay(overlay))
+            .collect::<DuckResult<Vec<DuckBill>>>()?;
+        DuckFile::try_from(bills)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::tests::get_test_data;
+
+    #[test]
+    fn presets() {
+        assert_eq!(DuckOverlay::duplicate().get_text(), "DUPLICATE");
+        assert_eq!(DuckOverlay::reprint("10/19/26").get_text(), "REPRINT 10/19/26");
+        assert_eq!(DuckOverlay::custom("VOID\r\x1bE").get_text(), "VOIDE");
+        assert_eq!(DuckOverlay::custom("REÇU ✓").get_text(), "RE?U ?");
+    }
+
+    #[test]
+    fn pcl_layout() {
+        let overlay = DuckOverlay::custom("COPY").at(100, 2000).size(24).rotation(90).unwrap();
+        assert_eq!(overlay.to_pcl().to_string(),
+                   "\x1b&f0S\x1b&a90P\x1b&a0100v2000H\x1b)8U\x1b)s1p24v0s3b4101T\x0eCOPY\x0f\x1b&a0P\x1b&f1S");
+        assert!(DuckOverlay::duplicate().rotation(45).is_err());
+        assert!(DuckOverlay::duplicate().rotation(360).is_err());
+    }
+
+    #[test]
+    fn original_content_untouched() {
+        let quack = get_test_data();
+        let stamped = quack.with_overlay(&DuckOverlay::duplicate()).unwrap();
+
+        assert_eq!(stamped.get_bill_count(), quack.get_bill_count());
+        for (n, bill) in stamped[..].iter().enumerate() {
+            let original = quack[n].get_raw().as_ref();
+            assert!(bill.get_raw().as_ref().starts_with(original));
+            assert_eq!(bill.get_account_id(), quack[n].get_account_id());
+            assert_eq!(bill.get_bill_number(), quack[n].get_bill_number());
+        }
+    }
+
+    #[test]
+    fn every_page_stamped() {
+        let mut data = get_test_data()[1].get_raw().clone();
+        let first_page = data.len();
+        data.push(DuckData::from("\x0cREMITTANCE COPY\x0c"));
+        let bill = DuckBill::new(data).unwrap();
+        assert_eq!(bill.page_count(), 2);
+
+        let stamp = DuckOverlay::duplicate().to_pcl();
+        let stamped = bill.with_overlay(&DuckOverlay::duplicate()).unwrap();
+        let raw = stamped.get_raw().as_ref();
+        // each stamp goes just before the form feed that ejects its page
+        assert_eq!(&raw[first_page..first_page + stamp.len()], stamp.as_ref());
+        assert_eq!(raw[first_page + stamp.len()], b'\x0c');
+        let second = raw.len() - 1 - stamp.len();
+        assert_eq!(&raw[second..raw.len() - 1], stamp.as_ref());
+        assert_eq!(&raw[first_page + stamp.len() + 1..second], b"REMITTANCE COPY");
+        assert_eq!(stamped.page_count(), 2);
+
+        // taking the stamps out gives back the original bill, and the stamps leave the
+        // primary font alone, so the second page prints in the bill's own font
+        let unstamped: Vec<u8> = [&raw[..first_page], &raw[first_page + stamp.len()..second], &raw[raw.len() - 1..]].concat();
+        assert_eq!(unstamped, bill.get_raw().as_ref());
+        assert!(!stamp.as_ref().windows(2).any(|w| w == b"\x1b("));
+        assert_eq!(stamp.as_ref().iter().filter(|&&b| b == 0x0e).count(), 1);
+        assert!(stamp.as_ref().ends_with(b"\x0f\x1b&a0P\x1b&f1S"));
+        assert_eq!(stamped.get_plain_text().iter().filter(|l| l.contains("REMITTANCE COPY")).count(), 1);
+        assert_eq!(stamped.get_account_id(), bill.get_account_id());
+    }
+}
@@ -0,0 +1,244 @@
+//! Pages within bills. A bill record usually prints as one page, but a record can
+//! eject pages of its own with form feeds, page-eject commands, or printer resets,
+//! and the footer prints the "TOTAL BILLS PRINTED" summary on a page of its own.
+//! Pages and bills are both numbered from 0, in file order.
+use std::ops::Range;
+use super::DuckFile;
+use super::duckbill::DuckBill;
+use super::duckpcl::{escape_commands, PclToken, PclTokens, FF};
+
+/// Find the pages printed by a PCL stream, as byte ranges of the stream.
+/// A page ends at a form feed, an "ESC &l0H" page eject, or an "ESC E" reset
+/// other than one at the very start. Breaks with nothing printed since the
+/// previous break don't eject anything, so they don't count as pages.
+pub fn page_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let mut pages = vec![];
+    let mut start = 0;
+    let mut marked = false;
+
+    let mut end_page = |start: &mut usize, end: usize, marked: &mut bool| {
+        if *marked {
+            pages.push(*start..end);
+        }
+        *start = end;
+        *marked = false;
+    };
+
+    for token in PclTokens::new(data) {
+        match token {
+            PclToken::Text(_) => marked = true,
+            PclToken::Control(at) => {
+                if data[at] == FF {
+                    end_page(&mut start, at + 1, &mut marked);
+                }
+            }
+            PclToken::Escape(r) => {
+                if &data[r.clone()] == b"\x1bE" {
+                    if r.start > 0 {
+                        end_page(&mut start, r.start, &mut marked);
+                    }
+                    continue;
+                }
+                if is_eject(&data[r.clone()]) {
+                    end_page(&mut start, r.end, &mut marked);
+                    continue;
+                }
+                match escape_commands(&data[r.clone()]) {
+                    // raster rows print, even with no text on the page
+                    Some(((b'*', b'b'), commands)) if commands.iter().any(|(v, c)| *c == b'W' && v.parse::<usize>().unwrap_or(0) > 0) => {
+                        marked = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    end_page(&mut start, data.len(), &mut marked);
+
+    pages
+}
+
+/// Whether an escape sequence is an "ESC &l0H" page eject
+fn is_eject(escape: &[u8]) -> bool {
+    matches!(escape_commands(escape), Some(((b'&', b'l'), commands)) if commands.iter().any(|(v, c)| *c == b'H' && v == "0"))
+}
+
+/// Whether a PCL stream turns on two-sided printing ("ESC &l1S" or "ESC &l2S") and leaves it on.
+/// A reset turns it back off.
+fn is_duplex(data: &[u8]) -> bool {
+    let mut duplex = false;
+    for token in PclTokens::new(data) {
+        if let PclToken::Escape(r) = token {
+            if &data[r.clone()] == b"\x1bE" {
+                duplex = false;
+            }
+            else if let Some(((b'&', b'l'), commands)) = escape_commands(&data[r]) {
+                for (v, c) in commands {
+                    if c == b'S' {
+                        duplex = v == "1" || v == "2";
+                    }
+                }
+            }
+        }
+    }
+    duplex
+}
+
+/// How pages map onto the bills of a file, and back
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuckPageMap {
+    first_pages: Vec<usize>,
+    page_count: usize,
+}
+
+impl DuckPageMap {
+    /// Get the number of bill pages; the footer's summary page isn't included
+    pub fn get_page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Get the pages printed by the bill at this index
+    pub fn get_pages_of_bill(&self, index: usize) -> Option<Range<usize>> {
+        let first = *self.first_pages.get(index)?;
+        let next = self.first_pages.get(index + 1).copied().unwrap_or(self.page_count);
+        Some(first..next)
+    }
+
+    /// Get the index of the bill that prints this page
+    pub fn get_bill_of_page(&self, page: usize) -> Option<usize> {
+        if page >= self.page_count {
+            return None;
+        }
+        // bills that print no pages share a first page with the next bill; take the last of them
+        Some(self.first_pages.partition_point(|&first| first <= page) - 1)
+    }
+}
+
+impl DuckBill {
+    /// Get the pages this bill prints, as byte ranges of get_raw()
+    pub fn pages(&self) -> Vec<Range<usize>> {
+        page_ranges(self.get_raw().as_ref())
+    }
+
+    /// Get the number of pages this bill prints
+    pub fn page_count(&self) -> usize {
+        self.pages().len()
+    }
+
+    /// Get where each page's content ends, as offsets into get_raw(): just before the form
+    /// feed or page eject that ends the page, or at the end of the page if a reset or the
+    /// end of the record ends it. Anything added there prints on that page.
+    pub fn page_ends(&self) -> Vec<usize> {
+        let data = self.get_raw().as_ref();
+        self.pages().into_iter().map(|page| {
+            match PclTokens::new(&data[page.clone()]).last() {
+                Some(PclToken::Control(at)) if data[page.start + at] == FF => page.start + at,
+                Some(PclToken::Escape(r)) if is_eject(&data[page.start + r.start..page.start + r.end]) => page.start + r.start,
+                _ => page.end,
+            }
+        }).collect()
+    }
+
+    /// Get the number of sheets of paper this bill uses, allowing for two-sided printing
+    pub fn sheet_count(&self) -> usize {
+        let pages = self.page_count();
+        if is_duplex(self.get_raw().as_ref()) {
+            pages.div_ceil(2)
+        } else {
+            pages
+        }
+    }
+}
+
+impl DuckFile {
+    /// Get the number of pages printed by all bills; the footer's summary page isn't included
+    pub fn page_count(&self) -> usize {
+        self.bills.iter().map(|b| b.page_count()).sum()
+    }
+
+    /// Get the number of sheets of paper used by all bills; the footer's summary page isn't included
+    pub fn sheet_count(&self) -> usize {
+        self.bills.iter().map(|b| b.sheet_count()).sum()
+    }
+
+    /// Get the number of pages printed by the footer
+    pub fn footer_page_count(&self) -> usize {
+        page_ranges(self.footer.as_ref()).len()
+    }
+
+    /// Work out which pages each bill prints
+    pub fn page_map(&self) -> DuckPageMap {
+        let mut first_pages = Vec::with_capacity(self.bills.len());
+        let mut page_count = 0;
+        for bill in &self.bills {
+            first_pages.push(page_count);
+            page_count += bill.page_count();
+        }
+        DuckPageMap { first_pages, page_count }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::duckdata::DuckData;
+    use crate::duckfile::tests::get_test_data;
+
+    #[test]
+    fn one_page_per_bill() {
+        let quack = get_test_data();
+        for bill in quack[..].iter() {
+            let pages = bill.pages();
+            assert_eq!(pages, vec![0..bill.get_raw().len()]);
+            assert_eq!(bill.sheet_count(), 1);
+        }
+        assert_eq!(quack.page_count(), 4);
+        assert_eq!(quack.footer_page_count(), 1);
+    }
+
+    #[test]
+    fn breaks_inside_a_record() {
+        let data = b"\x1bE\rPAGE ONE\x0cPAGE TWO\x1b&l0H\x1b&l0H\x0c\x1b*b2W\xff\xffPAGE THREE\x1bE\r";
+        assert_eq!(page_ranges(data), vec![0..12, 12..25, 31..48]);
+        // an FF carried as raster data isn't a break
+        assert_eq!(page_ranges(b"\x1b*b1W\x0cTEXT"), vec![0..10]);
+        assert!(page_ranges(b"\x1bE\r\x1b&l6D\r").is_empty());
+    }
+
+    #[test]
+    fn duplex_sheets() {
+        let raw = get_test_data()[0].get_raw().clone();
+        let mut data = DuckData::from(&raw[..3]);
+        data.push(DuckData::from("\x1b&l1S"));
+        data.push(DuckData::from(&raw[3..]));
+        data.push(DuckData::from("\x0cBACK\x0cNEXT"));
+        let bill = DuckBill::new(data).unwrap();
+        assert_eq!(bill.page_count(), 3);
+        assert_eq!(bill.sheet_count(), 2);
+
+        // duplex set before the reset doesn't survive it
+        let mut data = DuckData::from("\x1b&l1S");
+        data.push(raw);
+        assert_eq!(DuckBill::new(data).unwrap().sheet_count(), 1);
+    }
+
+    #[test]
+    fn map_both_ways() {
+        let quack = get_test_data();
+        let mut bills = quack[..].to_vec();
+        let mut extra = bills[1].get_raw().clone();
+        extra.push(DuckData::from("\x0cREMITTANCE COPY"));
+        bills[1] = DuckBill::new(extra).unwrap();
+        let quack = DuckFile::try_from(bills).unwrap();
+
+        let map = quack.page_map();
+        assert_eq!(map.get_page_count(), 5);
+        assert_eq!(map.get_pages_of_bill(0), Some(0..1));
+        assert_eq!(map.get_pages_of_bill(1), Some(1..3));
+        assert_eq!(map.get_pages_of_bill(3), Some(4..5));
+        assert_eq!(map.get_pages_of_bill(4), None);
+        let bills: Vec<Option<usize>> = (0..6).map(|p| map.get_bill_of_page(p)).collect();
+        assert_eq!(bills, vec![Some(0), Some(1), Some(1), Some(2), Some(3), None]);
+    }
+}
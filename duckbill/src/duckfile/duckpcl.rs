@@ -0,0 +1,252 @@
+//! Minimal PCL tokenizing: enough to step over escape sequences and find printed text.
+//! Bills are PCL 5 print streams; this doesn't interpret commands beyond what duckbill needs.
+use std::ops::Range;
+
+/// Byte that begins any PCL escape sequence
+pub const ESC: u8 = 0x1b;
+/// Carriage return, which ends most printed lines
+pub const CR: u8 = 0x0d;
+/// Line feed
+pub const LF: u8 = 0x0a;
+/// Form feed, which ejects the current page
+pub const FF: u8 = 0x0c;
+
+/// A piece of a PCL stream
+#[derive(Debug, PartialEq, Clone)]
+pub enum PclToken {
+    /// An escape sequence, including any binary data it carries
+    Escape(Range<usize>),
+    /// A run of printable text
+    Text(Range<usize>),
+    /// A single control character such as CR, LF, or FF
+    Control(usize),
+}
+
+/// Get the length of the escape sequence starting at `at`.
+/// Sequences that carry binary data (raster, font, and transparent print data)
+/// include that data. Truncated sequences end at the end of the data.
+pub fn escape_len(data: &[u8], at: usize) -> usize {
+    let rest = &data[at.min(data.len())..];
+    match rest.get(1) {
+        None => rest.len(),
+        // parameterized: ESC, parameter character, then values up to an uppercase terminator
+        Some(0x21..=0x2f) => {
+            match rest[2..].iter().position(|b| (0x40..=0x5e).contains(b)) {
+                None => rest.len(),
+                Some(t) => {
+                    let end = 2 + t + 1;
+                    let terminator = rest[end - 1];
+                    let carries_data = matches!((rest[1], terminator),
+                        (b'*' | b'(' | b')', b'W') | (b'&', b'X'));
+                    if carries_data {
+                        let bytes = trailing_value(&rest[2..end - 1]);
+                        end.saturating_add(bytes).min(rest.len())
+                    } else {
+                        end
+                    }
+                }
+            }
+        }
+        // two-character sequence, such as ESC E (printer reset)
+        Some(_) => 2,
+    }
+}
+
+/// Get the numeric value just before a sequence's terminator, e.g. 1024 in "*b1024W"
+fn trailing_value(params: &[u8]) -> usize {
+    let digits = params.iter().rev().take_while(|b| b.is_ascii_digit()).count();
+    params[params.len() - digits..].iter()
+        .fold(0usize, |n, b| n.saturating_mul(10).saturating_add((b - b'0') as usize))
+}
+
+/// Get the cursor position set by an escape sequence such as "ESC &a0165v0240H",
+/// as (vertical, horizontal) decipoints
+pub fn cursor_position(escape: &[u8]) -> Option<(u32, u32)> {
+    let params = escape.strip_prefix(b"\x1b&a")?.strip_suffix(b"H")?;
+    let v = params.iter().position(|&b| b == b'v')?;
+    let parse = |p: &[u8]| std::str::from_utf8(p).ok()?.parse::<u32>().ok();
+    Some((parse(&params[..v])?, parse(&params[v + 1..])?))
+}
+
+/// One command of a parameterized escape sequence: its value and uppercase command character
+pub type PclCommand = (String, u8);
+
+/// Split a parameterized escape sequence into its group, e.g. (b'&', b'l') for "ESC &l1s0H",
+/// and its commands as (value, uppercase command) pairs, e.g. [("1", b'S'), ("0", b'H')].
+/// Any data carried after the final command is ignored.
+pub fn escape_commands(escape: &[u8]) -> Option<((u8, u8), Vec<PclCommand>)> {
+    if escape.first() != Some(&ESC) || !(0x21..=0x2f).contains(escape.get(1)?) {
+        return None;
+    }
+    let group = (escape[1], *escape.get(2)?);
+    let mut commands = vec![];
+    let mut value = String::new();
+    for &b in &escape[3..] {
+        match b {
+            b'0'..=b'9' | b'.' | b'+' | b'-' => value.push(b as char),
+            b'a'..=b'z' => commands.push((std::mem::take(&mut value), b.to_ascii_uppercase())),
+            0x40..=0x5e => {
+                commands.push((value, b));
+                return Some((group, commands));
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Decipoints per line at the bills' 6 lines per inch, used to advance past line feeds
+const LINE_HEIGHT: u32 = 120;
+/// Decipoints per character column in a plain-text rendering
+const COLUMN_WIDTH: u32 = 60;
+/// Furthest column text is indented to, well past the edge of a landscape page;
+/// a cursor placed beyond it is taken to be at it
+const MAX_COLUMN: usize = 200;
+
+/// Render the printed text of a PCL stream as plain text lines, laid out roughly
+/// where the cursor positioning puts it. Fonts, graphics and rules are dropped.
+pub fn plain_text(data: &[u8]) -> Vec<String> {
+    // (vertical, horizontal) position, text; in the order printed
+    let mut pieces: Vec<(u32, u32, String)> = vec![];
+    let (mut v, mut h) = (0u32, 0u32);
+    for token in PclTokens::new(data) {
+        match token {
+            PclToken::Escape(range) => {
+                if let Some((to_v, to_h)) = cursor_position(&data[range]) {
+                    (v, h) = (to_v, to_h);
+                }
+            }
+            PclToken::Control(at) if data[at] == LF => v = v.saturating_add(LINE_HEIGHT),
+            PclToken::Control(at) if data[at] == CR => h = 0,
+            PclToken::Control(_) => {}
+            PclToken::Text(range) => {
+                let text = String::from_utf8_lossy(&data[range]).to_string();
+                let width = (text.chars().count() as u32).saturating_mul(COLUMN_WIDTH);
+                pieces.push((v, h, text));
+                h = h.saturating_add(width);
+            }
+        }
+    }
+    pieces.sort_by_key(|(v, h, _)| (*v, *h));
+
+    let mut lines: Vec<String> = vec![];
+    let mut line_v = None;
+    for (v, h, text) in pieces {
+        if line_v != Some(v) {
+            lines.push(String::new());
+            line_v = Some(v);
+        }
+        let line = lines.last_mut().expect("a line was just started");
+        let column = ((h / COLUMN_WIDTH) as usize).min(MAX_COLUMN);
+        let length = line.chars().count();
+        if column > length {
+            line.push_str(&" ".repeat(column - length));
+        } else if length > 0 && !line.ends_with(' ') && !text.starts_with(' ') {
+            line.push(' ');
+        }
+        line.push_str(&text);
+    }
+    lines.iter_mut().for_each(|line| *line = line.trim_end().to_string());
+    lines.retain(|line| !line.is_empty());
+    lines
+}
+
+/// Iterates over the tokens of a PCL stream
+pub struct PclTokens<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PclTokens<'a> {
+    pub fn new(data: &'a [u8]) -> PclTokens<'a> {
+        PclTokens { data, pos: 0 }
+    }
+}
+
+impl Iterator for PclTokens<'_> {
+    type Item = PclToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let b = *self.data.get(start)?;
+        let token = if b == ESC {
+            self.pos += escape_len(self.data, start).max(1);
+            PclToken::Escape(start..self.pos)
+        } else if b < 0x20 {
+            self.pos += 1;
+            PclToken::Control(start)
+        } else {
+            let len = self.data[start..].iter().position(|&c| c < 0x20).unwrap_or(self.data.len() - start);
+            self.pos += len;
+            PclToken::Text(start..self.pos)
+        };
+        Some(token)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_lengths() {
+        assert_eq!(escape_len(b"\x1bE\r", 0), 2);
+        assert_eq!(escape_len(b"\x1b&a0165v0240HBILL", 0), 13);
+        assert_eq!(escape_len(b"\x1b(s1p12v0s3b4101TCITY", 0), 17);
+        assert_eq!(escape_len(b"\x1b(8U", 0), 4);
+        assert_eq!(escape_len(b"\x1b*b3W\x1b\x1b\x1bTEXT", 0), 8);
+        // truncated sequences don't run past the end
+        assert_eq!(escape_len(b"\x1b&a01", 0), 5);
+        assert_eq!(escape_len(b"\x1b*b99W12", 0), 8);
+        assert_eq!(escape_len(b"\x1b", 0), 1);
+    }
+
+    #[test]
+    fn cursor_positions() {
+        assert_eq!(cursor_position(b"\x1b&a0165v0240H"), Some((165, 240)));
+        assert_eq!(cursor_position(b"\x1b(s3B"), None);
+        assert_eq!(cursor_position(b"\x1b&a01x5v0240H"), None);
+    }
+
+    #[test]
+    fn commands_split() {
+        assert_eq!(escape_commands(b"\x1b&l1s0H"),
+                   Some(((b'&', b'l'), vec![("1".to_string(), b'S'), ("0".to_string(), b'H')])));
+        assert_eq!(escape_commands(b"\x1b*b3W\x1b\x1b\x1b"),
+                   Some(((b'*', b'b'), vec![("3".to_string(), b'W')])));
+        assert_eq!(escape_commands(b"\x1bE"), None);
+        assert_eq!(escape_commands(b"\x1b&a01"), None);
+    }
+
+    #[test]
+    fn plain_text_follows_the_cursor() {
+        let data = b"\x1b&a0165v0240H\x1b(s3BBILL DATE \x1b&a0165v0825H4/01/21\x1b&a0120v0000HTOP\r\nNEXT\r\n\x1bE";
+        assert_eq!(plain_text(data), ["TOP", "    BILL DATE 4/01/21", "NEXT"]);
+    }
+
+    #[test]
+    fn plain_text_survives_hostile_positions() {
+        // far off the page, then text and line feeds that would run past u32::MAX
+        let mut data = b"\x1b&a0v4000000000HFAR\x1b&a4294967200v4294967295HEDGE".to_vec();
+        data.extend(b"\r\n".repeat(3));
+        data.extend(b"X".repeat(100));
+        assert_eq!(plain_text(&data), [format!("{}FAR", " ".repeat(MAX_COLUMN)),
+                                       format!("{}EDGE", " ".repeat(MAX_COLUMN)),
+                                       "X".repeat(100)]);
+    }
+
+    #[test]
+    fn tokens_cover_stream() {
+        let data = b"\x1b&a0165v0240H\x1b(s3BBILL DATE \r\n\x1bE";
+        let tokens: Vec<PclToken> = PclTokens::new(data).collect();
+        assert_eq!(tokens, vec![
+            PclToken::Escape(0..13),
+            PclToken::Escape(13..18),
+            PclToken::Text(18..28),
+            PclToken::Control(28),
+            PclToken::Control(29),
+            PclToken::Escape(30..32),
+        ]);
+    }
+}
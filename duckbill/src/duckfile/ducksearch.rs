@@ -0,0 +1,314 @@
+//! Fuzzy lookup of bills within a DuckFile.
+//! Operators mistype account numbers; this finds the accounts they most likely meant.
+//! Counter staff often know a name or street instead; text search covers that case.
+use super::DuckFile;
+
+/// Largest edit distance still considered a plausible typo
+pub const MAX_SUGGEST_DISTANCE: usize = 3;
+
+/// A bill that approximately matches a lookup
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuckSuggestion {
+    index: usize,
+    distance: usize,
+    transposed: bool,
+}
+
+impl DuckSuggestion {
+    /// Get the index of the suggested bill within the DuckFile
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the edit distance between the lookup and the suggested account
+    pub fn get_distance(&self) -> usize {
+        self.distance
+    }
+
+    /// True if the lookup is the suggested account with two adjacent digits swapped
+    pub fn is_transposition(&self) -> bool {
+        self.transposed
+    }
+}
+
+/// The bill field a text search matched
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DuckTextField {
+    Name,
+    ServiceAddress,
+    MailingAddress,
+}
+
+/// How closely a field matched a text search, weakest first
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum DuckTextScore {
+    /// every word of the search appears somewhere in the field
+    AllWords,
+    /// the search appears as a phrase within the field
+    Phrase,
+    /// the field starts with the search
+    Prefix,
+    /// the field is the search
+    Exact,
+}
+
+/// A bill found by text search
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuckTextMatch {
+    index: usize,
+    field: DuckTextField,
+    score: DuckTextScore,
+}
+
+impl DuckTextMatch {
+    /// Get the index of the matching bill within the DuckFile
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the field that matched best
+    pub fn get_field(&self) -> DuckTextField {
+        self.field
+    }
+
+    /// Get how closely the field matched
+    pub fn get_score(&self) -> DuckTextScore {
+        self.score
+    }
+}
+
+/// Normalize text for searching: uppercase, with runs of whitespace collapsed to one space
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| w.to_uppercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Score how well a normalized field matches a normalized search
+fn score_text(field: &str, wanted: &str) -> Option<DuckTextScore> {
+    if field == wanted {
+        Some(DuckTextScore::Exact)
+    } else if field.starts_with(wanted) {
+        Some(DuckTextScore::Prefix)
+    } else if field.contains(wanted) {
+        Some(DuckTextScore::Phrase)
+    } else if wanted.split(' ').all(|w| field.contains(w)) {
+        Some(DuckTextScore::AllWords)
+    } else {
+        None
+    }
+}
+
+/// Compute the edit distance between two byte strings.
+/// Insertions, deletions, substitutions, and swaps of adjacent bytes each count as one edit
+/// (optimal string alignment distance).
+pub fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let width = b.len() + 1;
+    let mut d = vec![0usize; (a.len() + 1) * width];
+
+    for i in 0..=a.len() {
+        d[i * width] = i;
+    }
+    for (j, cell) in d.iter_mut().enumerate().take(width) {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (d[(i - 1) * width + j] + 1)
+                .min(d[i * width + j - 1] + 1)
+                .min(d[(i - 1) * width + j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(d[(i - 2) * width + j - 2] + 1);
+            }
+            d[i * width + j] = best;
+        }
+    }
+
+    d[a.len() * width + b.len()]
+}
+
+/// Test if two byte strings differ only by one swap of adjacent bytes
+pub fn is_transposition(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diffs: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+
+    diffs.len() == 2
+        && diffs[1] == diffs[0] + 1
+        && a[diffs[0]] == b[diffs[1]]
+        && a[diffs[1]] == b[diffs[0]]
+}
+
+impl DuckFile {
+    /// Suggest bills whose account IDs are close to the (possibly mistyped) lookup.
+    /// Only digits are compared, so dashes and spaces in the lookup don't matter.
+    /// Results are ordered best first: by edit distance, with transposed digits
+    /// ahead of other single-digit mistakes. At most `max` suggestions are returned.
+    pub fn suggest_accounts(&self, lookup: &str, max: usize) -> Vec<DuckSuggestion> {
+        let wanted: Vec<u8> = lookup.bytes().filter(|b| b.is_ascii_digit()).collect();
+        if wanted.is_empty() {
+            return vec![];
+        }
+
+        let mut suggestions: Vec<DuckSuggestion> = self.bills.iter()
+            .enumerate()
+            .filter_map(|(index, bill)| {
+                let digits = bill.get_account_id().get_digits();
+                let distance = edit_distance(&wanted, &digits);
+                if distance <= MAX_SUGGEST_DISTANCE {
+                    Some(DuckSuggestion {
+                        index,
+                        distance,
+                        transposed: is_transposition(&wanted, &digits),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        suggestions.sort_by_key(|s| (s.distance, !s.transposed, s.index));
+        suggestions.truncate(max);
+        suggestions
+    }
+
+    /// Search bills by customer name, service address, or mailing address.
+    /// Matching ignores case and extra whitespace. Results are ranked best first;
+    /// bills with equally good matches stay in file order.
+    pub fn search_text(&self, search: &str) -> Vec<DuckTextMatch> {
+        let wanted = normalize_text(search);
+        if wanted.is_empty() {
+            return vec![];
+        }
+
+        let mut matches: Vec<DuckTextMatch> = self.bills.iter()
+            .enumerate()
+            .filter_map(|(index, bill)| {
+                let fields = [
+                    (DuckTextField::Name, bill.get_name().into_iter().collect::<Vec<String>>()),
+                    (DuckTextField::ServiceAddress, bill.get_service_address()),
+                    (DuckTextField::MailingAddress, bill.get_mailing_address()),
+                ];
+
+                let mut best: Option<DuckTextMatch> = None;
+                for (field, lines) in fields {
+                    let whole = normalize_text(&lines.join(" "));
+                    let score = lines.iter()
+                        .filter_map(|l| score_text(&normalize_text(l), &wanted))
+                        .chain(score_text(&whole, &wanted))
+                        .max();
+                    if let Some(score) = score {
+                        if best.as_ref().is_none_or(|b| score > b.score) {
+                            best = Some(DuckTextMatch { index, field, score });
+                        }
+                    }
+                }
+                best
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.index.cmp(&b.index)));
+        matches
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duckfile::tests::get_test_data;
+
+    #[test]
+    fn edit_distance_basics() {
+        assert_eq!(edit_distance(b"", b""), 0);
+        assert_eq!(edit_distance(b"abc", b""), 3);
+        assert_eq!(edit_distance(b"5211111111", b"5211111111"), 0);
+        assert_eq!(edit_distance(b"5211111111", b"5211111112"), 1);
+        assert_eq!(edit_distance(b"5211111111", b"521111111"), 1);
+        assert_eq!(edit_distance(b"1234567890", b"1243567890"), 1);
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+    }
+
+    #[test]
+    fn transposition_detected() {
+        assert!(is_transposition(b"1243567890", b"1234567890"));
+        assert!(!is_transposition(b"1234567890", b"1234567890"));
+        assert!(!is_transposition(b"1423567890", b"1234567890"));
+        assert!(!is_transposition(b"123456789", b"1234567890"));
+    }
+
+    #[test]
+    fn suggests_close_accounts() {
+        let quack = get_test_data();
+
+        let s = quack.suggest_accounts("52-1111112-1", 5);
+        assert_eq!(s[0].get_index(), 0);
+        assert_eq!(s[0].get_distance(), 1);
+
+        // dashes and spaces are ignored
+        let s = quack.suggest_accounts("52 333 3333 1", 5);
+        assert_eq!(s[0].get_index(), 2);
+        assert_eq!(s[0].get_distance(), 0);
+    }
+
+    #[test]
+    fn transpositions_rank_first() {
+        let quack = get_test_data();
+
+        let s = quack.suggest_accounts("25-2222222-1", 5);
+        assert_eq!(s[0].get_index(), 1);
+        assert!(s[0].is_transposition());
+    }
+
+    #[test]
+    fn normalize_text_collapses_whitespace() {
+        assert_eq!(normalize_text("  123   billone\tSt "), "123 BILLONE ST");
+    }
+
+    #[test]
+    fn search_by_name_and_address() {
+        let quack = get_test_data();
+
+        let m = quack.search_text("name number 22");
+        assert_eq!(m[0].get_index(), 1);
+        assert_eq!(m[0].get_field(), DuckTextField::Name);
+        assert_eq!(m[0].get_score(), DuckTextScore::Exact);
+
+        let m = quack.search_text("  billone   st");
+        assert_eq!(m.len(), 1);
+        assert_eq!(m[0].get_index(), 0);
+        assert_eq!(m[0].get_field(), DuckTextField::ServiceAddress);
+
+        let m = quack.search_text("sterling 01564");
+        assert_eq!(m.len(), 1);
+        assert_eq!(m[0].get_index(), 1);
+        assert_eq!(m[0].get_field(), DuckTextField::MailingAddress);
+    }
+
+    #[test]
+    fn search_ranks_better_matches_first() {
+        let quack = get_test_data();
+
+        // every bill has "NAME" in it, but only one name is exactly this
+        let m = quack.search_text("NAME NUMBER 11");
+        assert_eq!(m[0].get_index(), 0);
+        assert_eq!(m[0].get_score(), DuckTextScore::Exact);
+        assert!(m[1..].iter().all(|t| t.get_score() < DuckTextScore::Exact));
+
+        assert!(quack.search_text("nobody here").is_empty());
+        assert!(quack.search_text("   ").is_empty());
+    }
+
+    #[test]
+    fn distant_lookups_suggest_nothing() {
+        let quack = get_test_data();
+
+        assert!(quack.suggest_accounts("99-9999999-9", 5).is_empty());
+        assert!(quack.suggest_accounts("", 5).is_empty());
+        assert_eq!(quack.suggest_accounts("52-1111111-1", 1).len(), 1);
+    }
+}
@@ -0,0 +1,282 @@
+//! An append-only audit log of what was done with bill files: who extracted or printed
+//! which bills, when, from which file, and where they went.
+//!
+//! Each entry is one line of tab-separated fields:
+//!
+//!     sequence, timestamp, user, operation, input path, input SHA-256,
+//!     account IDs (comma-separated), bill count, output, output SHA-256,
+//!     previous entry's hash, this entry's hash
+//!
+//! An entry's hash covers all of its other fields, including the previous entry's hash,
+//! so editing, removing, or reordering entries breaks the chain from that point on.
+//! Removing entries from the very end can't be detected this way; keep copies of the log.
+//!
+//! Several copies of justducky (menus, extract, serve) can write to one log, so the log is
+//! locked while the last entry is read and the new one appended.
+use std::fmt::{Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckerror::DuckError;
+
+use crate::clock;
+
+/// The "previous hash" of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// Fields in an entry, including both hashes
+const FIELD_COUNT: usize = 12;
+
+/// Where the log is kept: $JUSTDUCKY_AUDIT_LOG if set, otherwise audit.log in the state folder
+pub fn default_path() -> PathBuf {
+    match std::env::var_os("JUSTDUCKY_AUDIT_LOG") {
+        Some(path) => PathBuf::from(path),
+        None => crate::state_dir().join("audit.log"),
+    }
+}
+
+/// Something done with a bill file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AuditOperation {
+    /// Kept the bills from a chosen bill to the end
+    Resume,
+    /// Kept the bills from the start to a chosen bill
+    Truncate,
+    Range,
+    Single,
+    /// Kept only the bills picked from a list
+    Picked,
+    /// Replaced the bulletin message on every bill
+    Bulletin,
+    /// Sent a processed file to a printer
+    Print,
+}
+
+impl Display for AuditOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AuditOperation::Resume => "resume",
+            AuditOperation::Truncate => "truncate",
+            AuditOperation::Range => "range",
+            AuditOperation::Single => "single",
+            AuditOperation::Picked => "picked",
+            AuditOperation::Bulletin => "bulletin",
+            AuditOperation::Print => "print",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The file an operation read from
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AuditSource {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// One thing to record
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditEntry<'a> {
+    pub operation: AuditOperation,
+    pub source: &'a AuditSource,
+    pub bills: &'a [DuckBill],
+    pub output: &'a str,
+    pub output_sha256: &'a str,
+}
+
+/// Where and why a log failed verification
+#[derive(Debug, PartialEq)]
+pub struct AuditFault {
+    /// Line number, counting from 1
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl Display for AuditFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Get the name of the operating system user running this program
+fn os_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Keep a field on one line and clear of the separator
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// Hash an entry's fields together with the previous entry's hash
+fn chain_hash(fields: &str, previous: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(fields.as_bytes());
+    hasher.update(b"\t");
+    hasher.update(previous.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Get the sequence number and hash of the last entry, or (0, GENESIS_HASH) for an empty log
+fn last_entry(log: &str) -> (u64, String) {
+    log.lines()
+        .last()
+        .and_then(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            Some((fields.first()?.parse().ok()?, fields.last()?.to_string()))
+        })
+        .unwrap_or((0, GENESIS_HASH.to_string()))
+}
+
+/// Add an entry to the end of the log, creating it if needed
+pub fn append(log_path: &Path, entry: &AuditEntry) -> Result<(), DuckError> {
+    append_as(log_path, entry, &clock::now_timestamp(), &os_user())
+}
+
+fn append_as(log_path: &Path, entry: &AuditEntry, timestamp: &str, user: &str) -> Result<(), DuckError> {
+    if let Some(dir) = log_path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    let mut log = OpenOptions::new().read(true).append(true).create(true).open(log_path)?;
+    // held until the entry is written and the log closed, so no one else takes the same sequence number
+    log.lock()?;
+    let mut existing = String::new();
+    log.read_to_string(&mut existing)?;
+    let (sequence, previous) = last_entry(&existing);
+
+    let accounts: Vec<String> = entry.bills.iter().map(|b| b.get_account_id().to_string()).collect();
+    let fields = [
+        (sequence + 1).to_string(),
+        timestamp.to_string(),
+        user.to_string(),
+        entry.operation.to_string(),
+        entry.source.path.display().to_string(),
+        entry.source.sha256.clone(),
+        accounts.join(","),
+        entry.bills.len().to_string(),
+        entry.output.to_string(),
+        entry.output_sha256.to_string(),
+    ].iter().map(|f| escape(f)).collect::<Vec<String>>().join("\t");
+    let hash = chain_hash(&fields, &previous);
+
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        writeln!(log)?;
+    }
+    writeln!(log, "{}\t{}\t{}", fields, previous, hash)?;
+    log.sync_data()?;
+    Ok(())
+}
+
+/// Check every entry's hash and its link to the one before.
+/// Returns the number of entries if the log is intact.
+pub fn verify(log: &str) -> Result<usize, AuditFault> {
+    let mut previous = GENESIS_HASH.to_string();
+    let mut count = 0;
+
+    for (n, line) in log.lines().enumerate() {
+        let fault = |reason| AuditFault { line: n + 1, reason };
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != FIELD_COUNT {
+            return Err(fault("wrong number of fields"));
+        }
+        if fields[0].parse::<usize>().ok() != Some(n + 1) {
+            return Err(fault("entry out of sequence"));
+        }
+        if fields[FIELD_COUNT - 2] != previous {
+            return Err(fault("doesn't follow the entry before it"));
+        }
+        let hash = chain_hash(&fields[..FIELD_COUNT - 2].join("\t"), &previous);
+        if fields[FIELD_COUNT - 1] != hash {
+            return Err(fault("entry has been changed"));
+        }
+        previous = hash;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckbill::duckfile::duckbillbuilder::synthetic_file;
+
+    fn scratch_log(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("justducky-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("audit.log")
+    }
+
+    fn write_entries(log_path: &Path) -> Vec<String> {
+        let bills = synthetic_file(4, 37).unwrap();
+        let source = AuditSource { path: PathBuf::from("/bills/april\tfinal.dat"), sha256: "ab".repeat(32) };
+        for (operation, sel) in [(AuditOperation::Single, &bills[1..2]), (AuditOperation::Range, &bills[1..3]), (AuditOperation::Print, &bills[1..3])] {
+            let entry = AuditEntry { operation, source: &source, bills: sel, output: "/bills/april.DUCKED", output_sha256: &"cd".repeat(32) };
+            append_as(log_path, &entry, "2021-04-01T13:05:09Z", "clerk").unwrap();
+        }
+        bills[..].iter().map(|b| b.get_account_id().to_string()).collect()
+    }
+
+    #[test]
+    fn entries_chain_and_verify() {
+        let log_path = scratch_log("audit");
+        let accounts = write_entries(&log_path);
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(verify(&log), Ok(3));
+
+        let first: Vec<&str> = log.lines().next().unwrap().split('\t').collect();
+        assert_eq!(first[..9], ["1", "2021-04-01T13:05:09Z", "clerk", "single", "/bills/april\\tfinal.dat",
+                                &"ab".repeat(32), &accounts[1], "1", "/bills/april.DUCKED"]);
+        assert_eq!(first[10], GENESIS_HASH);
+        assert_eq!(log.lines().nth(1).unwrap().split('\t').nth(6), Some(accounts[1..3].join(",").as_str()));
+        fs::remove_dir_all(log_path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn simultaneous_writers_keep_the_chain() {
+        let log_path = scratch_log("audit-locked");
+        let writers: Vec<_> = (0..4).map(|_| {
+            let log_path = log_path.clone();
+            std::thread::spawn(move || {
+                let bills = synthetic_file(1, 37).unwrap();
+                let source = AuditSource::default();
+                for _ in 0..10 {
+                    let entry = AuditEntry { operation: AuditOperation::Single, source: &source, bills: &bills[..], output: "out", output_sha256: "" };
+                    append(&log_path, &entry).unwrap();
+                }
+            })
+        }).collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        assert_eq!(verify(&fs::read_to_string(&log_path).unwrap()), Ok(40));
+        fs::remove_dir_all(log_path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn tampering_is_found() {
+        let log_path = scratch_log("audit-tamper");
+        write_entries(&log_path);
+        let log = fs::read_to_string(&log_path).unwrap();
+
+        let edited = log.replacen("clerk", "nobody", 2);
+        assert_eq!(verify(&edited), Err(AuditFault { line: 1, reason: "entry has been changed" }));
+
+        let lines: Vec<&str> = log.lines().collect();
+        let removed = format!("{}\n{}\n", lines[0], lines[2]);
+        assert_eq!(verify(&removed), Err(AuditFault { line: 2, reason: "entry out of sequence" }));
+
+        // renumbering to hide a removal still breaks the chain
+        let renumbered = removed.replacen("\n3\t", "\n2\t", 1);
+        assert_eq!(verify(&renumbered), Err(AuditFault { line: 2, reason: "doesn't follow the entry before it" }));
+
+        assert_eq!(verify(""), Ok(0));
+        fs::remove_dir_all(log_path.parent().unwrap()).unwrap();
+    }
+}
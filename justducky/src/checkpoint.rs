@@ -0,0 +1,282 @@
+//! A checkpoint journal for print jobs, so an interrupted job can pick up where it stopped.
+//! Each job gets a plain text file named after the SHA-256 hash of the bill file being printed.
+//! A line is added as each bill is handed to the printer's connection or pipe, and a final
+//! line when the job completes:
+//!
+//!     justducky checkpoint 1
+//!     file /bills/april.dat.DUCKED
+//!     sha256 9f86d081...
+//!     bills 1200
+//!     note sent bills were handed to the printer; the last few before a failure may not have printed
+//!     sent 0 52-1111111-1 7488
+//!     sent 1 52-2222222-1 6671
+//!     done
+//!
+//! Handing a bill over only means the operating system has it; it can still be sitting in a
+//! buffer, on the way to the printer, when the job fails. So a resumed job starts after the
+//! last bill recorded, but the last few bills recorded before the failure need checking.
+//!
+//! Once a job is done, or what was left of it has been resumed, its journal is no longer
+//! needed, and the journals of every such job are deleted.
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckerror::DuckError;
+
+const JOURNAL_FIRST_LINE: &str = "justducky checkpoint 1";
+const JOURNAL_EXTENSION: &str = "journal";
+/// Written in every journal for whoever reads it
+const SENT_NOTE: &str = "sent bills were handed to the printer; the last few before a failure may not have printed";
+
+/// Where journals are kept: $JUSTDUCKY_CHECKPOINTS if set, otherwise checkpoints in the state folder
+pub fn default_dir() -> PathBuf {
+    match std::env::var_os("JUSTDUCKY_CHECKPOINTS") {
+        Some(dir) => PathBuf::from(dir),
+        None => crate::state_dir().join("checkpoints"),
+    }
+}
+
+/// Get the SHA-256 hash of a bill file's contents as lowercase hex
+pub fn file_hash(bills: &DuckFile) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bills.get_header().as_ref());
+    for bill in bills[..].iter() {
+        hasher.update(bill.get_raw().as_ref());
+    }
+    hasher.update(bills.get_footer().as_ref());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Get the SHA-256 hash of some bytes, such as a file as it was written, as lowercase hex
+pub fn data_hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Delete the journals of jobs that were done or resumed. Journals that can't be read are left alone.
+pub fn prune_finished(dir: &Path) -> Result<(), DuckError> {
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_some_and(|x| x == JOURNAL_EXTENSION) && Checkpoint::read(&path).is_ok_and(|c| c.finished) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// A journal being written while a job prints
+pub struct CheckpointJournal {
+    file: File,
+    path: PathBuf,
+}
+
+impl CheckpointJournal {
+    /// Start a fresh journal for printing `bills`, read from `printed`.
+    /// Any earlier journal for the same file is replaced.
+    pub fn start(dir: &Path, printed: &Path, bills: &DuckFile) -> Result<CheckpointJournal, DuckError> {
+        fs::create_dir_all(dir)?;
+        let hash = file_hash(bills);
+        let path = dir.join(format!("{}.{}", hash, JOURNAL_EXTENSION));
+        let mut file = File::create(&path)?;
+        let printed = fs::canonicalize(printed).unwrap_or(printed.to_path_buf());
+        write!(file, "{}\nfile {}\nsha256 {}\nbills {}\nnote {}\n",
+               JOURNAL_FIRST_LINE, printed.display(), hash, bills.get_bill_count(), SENT_NOTE)?;
+        file.sync_data()?;
+        Ok(CheckpointJournal { file, path })
+    }
+
+    /// Record that the bill at this index has been completely handed to the printer
+    pub fn record(&mut self, index: usize, bill: &DuckBill) -> Result<(), DuckError> {
+        writeln!(self.file, "sent {} {} {}", index, bill.get_account_id(), bill.get_bill_number())?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Record that the whole job was sent, and clear away finished journals
+    pub fn finish(mut self) -> Result<(), DuckError> {
+        writeln!(self.file, "done")?;
+        self.file.sync_data()?;
+        prune_finished(self.path.parent().unwrap_or(Path::new(".")))
+    }
+}
+
+/// What a journal says about a job
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checkpoint {
+    journal: PathBuf,
+    printed: PathBuf,
+    hash: String,
+    bill_count: usize,
+    last_sent: Option<(usize, String, u32)>,
+    finished: bool,
+}
+
+impl Checkpoint {
+    /// Read a journal. Lines cut short by a crash are ignored.
+    pub fn read(journal: &Path) -> Result<Checkpoint, DuckError> {
+        let text = fs::read_to_string(journal)?;
+        let mut lines = text.lines();
+        if lines.next() != Some(JOURNAL_FIRST_LINE) {
+            return Err(DuckError::BadData);
+        }
+
+        let mut checkpoint = Checkpoint {
+            journal: journal.to_path_buf(),
+            printed: PathBuf::new(),
+            hash: String::new(),
+            bill_count: 0,
+            last_sent: None,
+            finished: false,
+        };
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "file" => checkpoint.printed = PathBuf::from(value),
+                "sha256" => checkpoint.hash = value.to_string(),
+                "bills" => checkpoint.bill_count = value.parse()?,
+                "sent" => {
+                    let fields: Vec<&str> = value.split(' ').collect();
+                    if let [index, acct, bill_number] = fields[..] {
+                        if let (Ok(index), Ok(bill_number)) = (index.parse(), bill_number.parse()) {
+                            checkpoint.last_sent = Some((index, acct.to_string(), bill_number));
+                        }
+                    }
+                }
+                "done" | "resumed" => checkpoint.finished = true,
+                _ => {}
+            }
+        }
+        if checkpoint.hash.is_empty() || checkpoint.printed.as_os_str().is_empty() {
+            return Err(DuckError::BadData);
+        }
+        Ok(checkpoint)
+    }
+
+    /// Find the most recently written journal for a job that didn't finish
+    pub fn last_interrupted(dir: &Path) -> Option<Checkpoint> {
+        fs::read_dir(dir).ok()?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|x| x == JOURNAL_EXTENSION))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, Checkpoint::read(&e.path()).ok()?)))
+            .filter(|(_, c)| !c.finished)
+            .max_by_key(|(modified, _)| *modified)
+            .map(|(_, c)| c)
+    }
+
+    /// Get the bill file the job was printing
+    pub fn get_printed_file(&self) -> &Path {
+        &self.printed
+    }
+
+    /// Get the number of bills in the job
+    pub fn get_bill_count(&self) -> usize {
+        self.bill_count
+    }
+
+    /// Get the account ID and bill number of the last bill handed to the printer, if any were
+    pub fn get_last_sent(&self) -> Option<(&str, u32)> {
+        self.last_sent.as_ref().map(|(_, acct, n)| (acct.as_str(), *n))
+    }
+
+    /// Get the index of the first bill still to print, after checking that
+    /// `bills` is the file the job was printing and the journal agrees with it
+    pub fn get_resume_index(&self, bills: &DuckFile) -> Result<usize, DuckError> {
+        if file_hash(bills) != self.hash || bills.get_bill_count() != self.bill_count {
+            return Err(DuckError::BadData);
+        }
+        match &self.last_sent {
+            None => Ok(0),
+            Some((index, acct, bill_number)) => {
+                if *index >= bills.get_bill_count() {
+                    return Err(DuckError::BadData);
+                }
+                let bill = &bills[*index];
+                if bill.get_account_id().to_string() != *acct || bill.get_bill_number() != *bill_number {
+                    return Err(DuckError::BadData);
+                }
+                Ok(index + 1)
+            }
+        }
+    }
+
+    /// Note in the journal that the rest of the job has been taken care of,
+    /// so it isn't offered again, and clear away finished journals
+    pub fn mark_resumed(&self) -> Result<(), DuckError> {
+        // a crash can leave the last line unfinished
+        let unfinished = !fs::read(&self.journal)?.ends_with(b"\n");
+        let mut file = OpenOptions::new().append(true).open(&self.journal)?;
+        writeln!(file, "{}resumed", if unfinished { "\n" } else { "" })?;
+        prune_finished(self.journal.parent().unwrap_or(Path::new(".")))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckbill::duckfile::duckbillbuilder::synthetic_file;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("justducky-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn interrupted_job_resumes_after_last_bill_sent() {
+        let dir = scratch_dir("checkpoint");
+        let bills = synthetic_file(6, 36).unwrap();
+        let printed = dir.join("bills.DUCKED");
+
+        let mut journal = CheckpointJournal::start(&dir, &printed, &bills).unwrap();
+        for n in 0..3 {
+            journal.record(n, &bills[n]).unwrap();
+        }
+        drop(journal);
+        // a crash partway through writing a line leaves a fragment
+        let journal_path = dir.join(format!("{}.journal", file_hash(&bills)));
+        OpenOptions::new().append(true).open(&journal_path).unwrap().write_all(b"sent 3 52-").unwrap();
+
+        let checkpoint = Checkpoint::last_interrupted(&dir).unwrap();
+        assert_eq!(checkpoint.get_printed_file(), printed);
+        assert_eq!(checkpoint.get_bill_count(), 6);
+        assert_eq!(checkpoint.get_last_sent(), Some((bills[2].get_account_id().to_string().as_str(), bills[2].get_bill_number())));
+        assert_eq!(checkpoint.get_resume_index(&bills), Ok(3));
+
+        // a different file isn't resumed with this journal
+        assert_eq!(checkpoint.get_resume_index(&synthetic_file(6, 99).unwrap()), Err(DuckError::BadData));
+
+        checkpoint.mark_resumed().unwrap();
+        assert_eq!(Checkpoint::last_interrupted(&dir), None);
+        assert!(!journal_path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finished_jobs_are_not_offered() {
+        let dir = scratch_dir("checkpoint-done");
+        let bills = synthetic_file(2, 36).unwrap();
+
+        let mut journal = CheckpointJournal::start(&dir, &dir.join("a.DUCKED"), &bills).unwrap();
+        journal.record(0, &bills[0]).unwrap();
+        journal.record(1, &bills[1]).unwrap();
+        // a job left behind marked done is cleared away along with this one
+        fs::write(dir.join("old.journal"), format!("{}\nfile /old\nsha256 ab\nbills 1\ndone\n", JOURNAL_FIRST_LINE)).unwrap();
+        fs::write(dir.join("notes.txt"), "kept").unwrap();
+        journal.finish().unwrap();
+        assert_eq!(Checkpoint::last_interrupted(&dir), None);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // nothing sent at all resumes from the start
+        CheckpointJournal::start(&dir, &dir.join("a.DUCKED"), &bills).unwrap();
+        let checkpoint = Checkpoint::last_interrupted(&dir).unwrap();
+        assert_eq!(checkpoint.get_last_sent(), None);
+        assert_eq!(checkpoint.get_resume_index(&bills), Ok(0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Non-interactive extraction for shell pipelines:
+//!
+//!     justducky extract [--from ACCT] [--to ACCT] [--only ACCT] [--gzip] [-o OUTPUT] INPUT
+//!
+//! INPUT may be - to read the bill file from stdin, and may be gzipped or zipped. The result
+//! goes to stdout unless -o names a file, which is replaced if it exists, and -o - also means
+//! stdout. Output files ending in .gz or .zip are compressed; --gzip compresses output to
+//! stdout. Messages for people always go to stderr, so they never mix with the bill data.
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckacctid::DuckAcctId;
+use duckbill::duckfile::duckcompress::DuckCompression;
+use duckbill::duckfile::duckerror::DuckError;
+
+use crate::audit::{self, AuditEntry, AuditOperation, AuditSource};
+use crate::checkpoint;
+use crate::output::{OutputPolicy, DEFAULT_TEMPLATE};
+
+/// The path that means stdin or stdout
+pub const STDIO_PATH: &str = "-";
+
+pub const USAGE: &str = "usage: justducky extract [--from ACCT] [--to ACCT] [--only ACCT] [--gzip] [-o OUTPUT] INPUT
+  INPUT        bill file to read (plain, gzip or zip), or - for stdin
+  --from ACCT  keep bills from this account to the end
+  --to ACCT    keep bills from the start to this account
+  --only ACCT  keep just this account's bill
+  --gzip       compress the result written to stdout
+  -o OUTPUT    where to write the result, or - for stdout (the default);
+               an existing file is replaced, and names ending in .gz or .zip
+               are compressed";
+
+/// What to extract, and from and to where
+#[derive(Debug, PartialEq, Default)]
+pub struct ExtractArgs {
+    pub input: String,
+    pub output: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub only: Option<String>,
+    pub gzip: bool,
+}
+
+/// Read the arguments that follow "extract"
+pub fn parse_args(args: &[String]) -> Result<ExtractArgs, String> {
+    let mut parsed = ExtractArgs { output: STDIO_PATH.to_string(), ..Default::default() };
+    let mut input = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--from" => parsed.from = Some(value("--from")?),
+            "--to" => parsed.to = Some(value("--to")?),
+            "--only" => parsed.only = Some(value("--only")?),
+            "-o" | "--output" => parsed.output = value("-o")?,
+            "-z" | "--gzip" => parsed.gzip = true,
+            a if a.starts_with('-') && a != STDIO_PATH => return Err(format!("unknown option {}", a)),
+            a => {
+                if input.replace(a.to_string()).is_some() {
+                    return Err("only one input file can be given".to_string());
+                }
+            }
+        }
+    }
+
+    parsed.input = input.ok_or("no input file given")?;
+    if parsed.only.is_some() && (parsed.from.is_some() || parsed.to.is_some()) {
+        return Err("--only can't be combined with --from or --to".to_string());
+    }
+    Ok(parsed)
+}
+
+/// Find the bill for an account ID given on the command line
+fn find_account(bills: &DuckFile, id: &str) -> Result<usize, String> {
+    DuckAcctId::normalize(id).ok()
+        .and_then(|acct| bills.get_index_of_account(&acct))
+        .ok_or(format!("account {} not found", id))
+}
+
+/// Work out which bills to keep, and what kind of extraction that is
+pub fn select(bills: &DuckFile, args: &ExtractArgs) -> Result<(AuditOperation, Range<usize>), String> {
+    if let Some(id) = &args.only {
+        let i = find_account(bills, id)?;
+        return Ok((AuditOperation::Single, i..i + 1));
+    }
+    let from = args.from.as_deref().map(|id| find_account(bills, id)).transpose()?;
+    let to = args.to.as_deref().map(|id| find_account(bills, id)).transpose()?;
+    match (from, to) {
+        (Some(start), Some(end)) if start > end => Err("--to account comes before --from account".to_string()),
+        (Some(start), Some(end)) => Ok((AuditOperation::Range, start..end + 1)),
+        (Some(start), None) => Ok((AuditOperation::Resume, start..bills.get_bill_count())),
+        (None, Some(end)) => Ok((AuditOperation::Truncate, 0..end + 1)),
+        (None, None) => Ok((AuditOperation::Range, 0..bills.get_bill_count())),
+    }
+}
+
+/// Describe why a bill file couldn't be read
+fn read_error(e: DuckError) -> String {
+    match e {
+        DuckError::FileTooSmall => "input is too short to be a bill file".to_string(),
+        DuckError::FileTooBig => "input is too large to be a bill file".to_string(),
+        DuckError::IoError => "input could not be read".to_string(),
+        e => format!("input is not a valid bill file ({:?})", e),
+    }
+}
+
+/// Run an extraction, returning the process exit code
+pub fn run(args: &[String]) -> i32 {
+    match extract(args, io::stdin().lock(), io::stdout().lock(), &audit::default_path()) {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("justducky: {}", message);
+            2
+        }
+    }
+}
+
+/// Extract bills, using `stdin` and `stdout` where the arguments say -
+fn extract<R: Read, W: Write>(args: &[String], stdin: R, mut stdout: W, audit_log: &Path) -> Result<(), String> {
+    let args = parse_args(args).map_err(|e| format!("{}\n{}", e, USAGE))?;
+
+    let bills = if args.input == STDIO_PATH {
+        DuckFile::read_from(stdin)
+    } else {
+        File::open(&args.input).map_err(DuckError::from).and_then(DuckFile::read_from)
+    }.map_err(read_error)?;
+    let source = AuditSource {
+        path: if args.input == STDIO_PATH { PathBuf::from(STDIO_PATH) } else { std::fs::canonicalize(&args.input).unwrap_or(PathBuf::from(&args.input)) },
+        sha256: checkpoint::file_hash(&bills),
+    };
+
+    let (operation, range) = select(&bills, &args)?;
+    let processed = DuckFile::try_from(bills[range].to_vec()).map_err(|e| format!("could not build the output ({:?})", e))?;
+
+    let (written, output_hash) = if args.output == STDIO_PATH {
+        let compression = if args.gzip { DuckCompression::Gzip } else { DuckCompression::None };
+        let mut data = vec![];
+        processed.write_compressed_to(&mut data, compression, "")
+            .map_err(|e| format!("could not build the output ({:?})", e))?;
+        stdout.write_all(&data).and_then(|_| stdout.flush())
+            .map_err(|_| "could not write to stdout".to_string())?;
+        (STDIO_PATH.to_string(), checkpoint::data_hash(&data))
+    } else {
+        // the name was asked for, so it's used as given, replacing any file already there
+        let output = OutputPolicy::new(DEFAULT_TEMPLATE, true)
+            .and_then(|policy| policy.write(Path::new(&args.output), &processed))
+            .map_err(|_| format!("could not write {}", args.output))?;
+        (output.path.display().to_string(), output.sha256)
+    };
+
+    let entry = AuditEntry { operation, source: &source, bills: &processed[..], output: &written, output_sha256: &output_hash };
+    if audit::append(audit_log, &entry).is_err() {
+        eprintln!("justducky: WARNING: this extraction could not be recorded in the audit log {}", audit_log.display());
+    }
+    eprintln!("justducky: {} bills written to {}", processed.get_bill_count(), if written == STDIO_PATH { "stdout" } else { &written });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckbill::duckfile::duckbillbuilder::synthetic_file;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_parse() {
+        let parsed = parse_args(&args(&["--from", "52-1111111-1", "-"])).unwrap();
+        assert_eq!(parsed.input, "-");
+        assert_eq!(parsed.output, "-");
+        assert_eq!(parsed.from.as_deref(), Some("52-1111111-1"));
+
+        let parsed = parse_args(&args(&["in.dat", "-o", "out.pcl", "--only", "5211111111"])).unwrap();
+        assert_eq!((parsed.input.as_str(), parsed.output.as_str()), ("in.dat", "out.pcl"));
+        assert!(!parsed.gzip);
+        assert!(parse_args(&args(&["--gzip", "-"])).unwrap().gzip);
+
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["a", "b"])).is_err());
+        assert!(parse_args(&args(&["--from"])).is_err());
+        assert!(parse_args(&args(&["--bogus", "a"])).is_err());
+        assert!(parse_args(&args(&["--only", "x", "--to", "y", "a"])).is_err());
+    }
+
+    #[test]
+    fn selections() {
+        let bills = synthetic_file(5, 39).unwrap();
+        let acct = |i: usize| Some(bills[i].get_account_id().to_string());
+
+        let only = ExtractArgs { only: acct(2), ..Default::default() };
+        assert_eq!(select(&bills, &only), Ok((AuditOperation::Single, 2..3)));
+        let range = ExtractArgs { from: acct(1), to: acct(3), ..Default::default() };
+        assert_eq!(select(&bills, &range), Ok((AuditOperation::Range, 1..4)));
+        let resume = ExtractArgs { from: acct(3), ..Default::default() };
+        assert_eq!(select(&bills, &resume), Ok((AuditOperation::Resume, 3..5)));
+        let truncate = ExtractArgs { to: acct(0), ..Default::default() };
+        assert_eq!(select(&bills, &truncate), Ok((AuditOperation::Truncate, 0..1)));
+
+        let backwards = ExtractArgs { from: acct(3), to: acct(1), ..Default::default() };
+        assert!(select(&bills, &backwards).is_err());
+        let missing = ExtractArgs { only: Some("00-0000000-0".to_string()), ..Default::default() };
+        assert!(select(&bills, &missing).is_err());
+    }
+
+    #[test]
+    fn stdin_to_stdout() {
+        let dir = std::env::temp_dir().join(format!("justducky-cli-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("audit.log");
+        let bills = synthetic_file(4, 39).unwrap();
+        let mut input = vec![];
+        bills.write_to(&mut input).unwrap();
+        let from = bills[1].get_account_id().to_string();
+
+        let mut output = vec![];
+        extract(&args(&["--from", &from, "-"]), &input[..], &mut output, &log).unwrap();
+        let kept = DuckFile::read_from(&output[..]).unwrap();
+        assert_eq!(kept.get_bill_count(), 3);
+        assert_eq!(kept[0].get_account_id(), bills[1].get_account_id());
+        assert!(std::fs::read_to_string(&log).unwrap().contains(&checkpoint::data_hash(&output)));
+
+        let mut zipped = vec![];
+        extract(&args(&["--gzip", "--only", &from, "-"]), &input[..], &mut zipped, &log).unwrap();
+        assert_eq!(DuckCompression::detect(&zipped), DuckCompression::Gzip);
+        assert_eq!(DuckFile::read_from(&zipped[..]).unwrap().get_bill_count(), 1);
+        assert_eq!(audit::verify(&std::fs::read_to_string(&log).unwrap()), Ok(2));
+
+        // a named output replaces what's there instead of picking another name
+        let named = dir.join("out.pcl");
+        for _ in 0..2 {
+            extract(&args(&["--only", &from, "-o", named.to_str().unwrap(), "-"]), &input[..], io::sink(), &log).unwrap();
+        }
+        assert_eq!(DuckFile::read_from(File::open(&named).unwrap()).unwrap().get_bill_count(), 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+//! Just enough calendar math to print today's date without pulling in a date crate.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil, in reverse
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Seconds since 1970-01-01 UTC
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Today's date (UTC) as (year, month, day)
+pub fn today() -> (i64, u32, u32) {
+    civil_from_days((now_secs() / 86_400) as i64)
+}
+
+/// Format seconds since 1970-01-01 as an ISO 8601 UTC timestamp, e.g. 2021-04-01T13:05:09Z
+pub fn timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The current time as an ISO 8601 UTC timestamp
+pub fn now_timestamp() -> String {
+    timestamp(now_secs())
+}
+
+/// Today's date the way bills print dates, e.g. 4/01/21
+pub fn today_short() -> String {
+    let (year, month, day) = today();
+    format!("{}/{:02}/{:02}", month, day, year.rem_euclid(100))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(18_718), (2021, 4, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(timestamp(18_718 * 86_400 + 13 * 3600 + 5 * 60 + 9), "2021-04-01T13:05:09Z");
+    }
+}
@@ -1,7 +1,16 @@
 use std::fs::File;
-use std::io;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+mod audit;
+mod checkpoint;
+mod cli;
+mod clock;
+mod output;
+mod printer;
+mod serve;
+#[cfg(feature="tui")]
+mod tui;
 
 #[cfg(feature="native-ui")]
 use native_dialog::FileDialog;
@@ -9,205 +18,1086 @@ use native_dialog::FileDialog;
 use duckbill::duckfile;
 use duckbill::duckfile::duckacctid::DuckAcctId;
 use duckbill::duckfile::duckbill::DuckBill;
-use duckbill::duckfile::duckdata::DuckData;
 use duckbill::duckfile::duckerror::DuckError;
+use duckbill::duckfile::duckmeter::format_amount;
+use duckbill::duckfile::duckoverlay::DuckOverlay;
 use duckfile::DuckFile;
-use m_menu::MMenu;
+use m_menu::{Console, MMenu, MenuOutcome, Picker, Prompt};
+use audit::{AuditEntry, AuditOperation, AuditSource};
+use checkpoint::{Checkpoint, CheckpointJournal};
+use output::OutputPolicy;
+use printer::{LpPrinter, PrintBackend, RawTcpPrinter, SpoolDirPrinter};
+
+/// Where justducky keeps its journals and logs: .justducky in the user's home folder
+/// (or the current folder if there isn't one)
+fn state_dir() -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    home.map(PathBuf::from).unwrap_or_default().join(".justducky")
+}
+
+/// Everything the menus work on
+struct Session {
+    /// the loaded bill file, and where it was loaded from
+    original_bills: DuckFile,
+    loaded_path: Option<PathBuf>,
+    /// the last processed file written, which is what gets printed
+    output_filename: PathBuf,
+    policy: OutputPolicy,
+    source: AuditSource,
+    /// where extracts and prints are recorded, and where print jobs keep their journals
+    audit_log: PathBuf,
+    checkpoint_dir: PathBuf,
+    /// the print job that can be resumed; looked for at the start and after each print or resume
+    interrupted_job: Option<Checkpoint>,
+    /// bills marked in the browser as the start and end of a range to extract
+    range_start: Option<usize>,
+    range_end: Option<usize>,
+    /// whether bill files are picked in the desktop's file dialog rather than typed in;
+    /// only the native-ui build turns this on, and only for a real session
+    file_dialog: bool,
+}
+
+impl Session {
+    fn new(policy: OutputPolicy, audit_log: PathBuf, checkpoint_dir: PathBuf) -> Session {
+        Session {
+            original_bills: DuckFile::new(),
+            loaded_path: None,
+            output_filename: PathBuf::new(),
+            policy,
+            source: AuditSource::default(),
+            audit_log,
+            interrupted_job: Checkpoint::last_interrupted(&checkpoint_dir),
+            checkpoint_dir,
+            range_start: None,
+            range_end: None,
+            file_dialog: false,
+        }
+    }
+
+    fn file_ready(&self) -> bool {
+        self.loaded_path.is_some()
+    }
+
+    /// Make a bill file the one being worked on
+    fn load(&mut self, filepath: &Path, bills: DuckFile) {
+        self.source = audit_source(filepath, &bills);
+        self.original_bills = bills;
+        self.loaded_path = Some(filepath.to_path_buf());
+        self.output_filename = PathBuf::new();
+        self.range_start = None;
+        self.range_end = None;
+    }
+
+    /// Write some of the loaded bills to a new processed file
+    fn create_output(&mut self, con: &mut Console, bill_sel: Vec<DuckBill>, operation: AuditOperation) -> Result<(), DuckError> {
+        create_output_file(con, &self.policy, &mut self.output_filename, bill_sel, operation, &self.source, &self.audit_log)
+    }
+
+    /// Whether there's a print job that was interrupted and can be resumed
+    fn has_interrupted_job(&self) -> bool {
+        self.interrupted_job.is_some()
+    }
+
+    /// Look through the journals again, after a print job has started or been resumed
+    fn refresh_interrupted_job(&mut self) {
+        self.interrupted_job = Checkpoint::last_interrupted(&self.checkpoint_dir);
+    }
+}
 
 fn main() -> Result<(), DuckError> {
-    let mut main_menu = MMenu::new();
-    main_menu.add_entry("1", "Select bill file", true);
-    main_menu.add_entry("2", "Skip from start to specified bill (resume print job)", false);
-    main_menu.add_entry("3", "Skip from specified bill to end", false);
-    main_menu.add_entry("4", "Extract range of bills", false);
-    main_menu.add_entry("5", "Extract single bill", false);
-
-    let mut file_ready = false;
-    let mut original_bills = DuckFile::new();
-    let mut output_filename = PathBuf::new();
-
-    println!("Welcome to the Just Ducky Second Chance Bill Handler");
-    println!("====================================================");
-    // main menu ui loop
-    loop {
-        main_menu.set_choice_avail("2", file_ready).expect("Menu 2 avail error");
-        main_menu.set_choice_avail("3", file_ready).expect("Menu 3 avail error");
-        main_menu.set_choice_avail("4", file_ready).expect("Menu 4 avail error");
-        main_menu.set_choice_avail("5", file_ready).expect("Menu 5 avail error");
-
-        println!("\nMain Menu");
-        println!("=========");
-        println!("{}", main_menu);
-        print!("Select number (q to quit): ");
-        io::stdout().flush()?;
-        let mut choice = String::new();
-        std::io::stdin().read_line(&mut choice)?;
-
-        if !main_menu.valid_choice(&choice.trim()) {
-            println!("Bye!");
-            return Ok(());
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("--verify-audit") => {
+            let log_path = args.get(2).map(PathBuf::from).unwrap_or_else(audit::default_path);
+            verify_audit_log(&log_path);
         }
-        println!("");
+        Some("extract") => std::process::exit(cli::run(&args[2..])),
+        Some("serve") => std::process::exit(serve::run(&args[2..])),
+        _ => {}
+    }
 
+    let policy = OutputPolicy::from_env().unwrap_or_else(|_| {
+        println!("JUSTDUCKY_OUTPUT_TEMPLATE isn't a usable name template; using {}", output::DEFAULT_TEMPLATE);
+        OutputPolicy::default()
+    });
+    let mut session = Session::new(policy, audit::default_path(), checkpoint::default_dir());
+    session.file_dialog = cfg!(feature="native-ui");
+    // --plain asks for the menus even where the full-screen interface would work
+    #[cfg(feature="tui")]
+    if args.get(1).is_none_or(|a| a != "--plain") && tui::available() {
+        return tui::run(&mut session);
+    }
+    run_session(&mut session, &mut Console::stdio())
+}
 
-        match choice.trim() {
-            "1" => {
-                let mut file_choice: Option<PathBuf> = None;
-                #[cfg(feature="native-ui")] {
-                    println!("Use the dialog to select a file.");
-                    let dialog_sel = FileDialog::new()
-                            .set_location("~")
-                            .show_open_single_file();
-                    if let Ok(maybe_path) = dialog_sel {
-                        file_choice = maybe_path;
-                    }
-                    else {
-                        println!("No filename given, returning to menu.");
-                    }
-                }
-                #[cfg(not(feature="native-ui"))] {
-                    print!("Enter path to file (press enter to cancel): ");
-                    io::stdout().flush()?;
-                    let mut file_input = String::new();
-                    io::stdin().read_line(&mut file_input)?;
-                    file_choice = Some(PathBuf::from(&file_input));
-                }
+/// Greet the operator and run the menus until they quit
+fn run_session(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    writeln!(con, "Welcome to the Just Ducky Second Chance Bill Handler")?;
+    writeln!(con, "====================================================")?;
+    main_menu().run_with(session, con)?;
+    writeln!(con, "Bye!")?;
+    Ok(())
+}
+
+/// Build the menus: the main menu, with sub-screens for extracting and printing
+fn main_menu() -> MMenu<Session, DuckError> {
+    let mut extract_menu = MMenu::titled("Extract Bills");
+    extract_menu.add_section("Several bills");
+    extract_menu.add_action("1", "Skip from start to specified bill (resume print job)", |s: &mut Session, con: &mut Console| {
+        if let Some(bill_idx) = select_resume_bill(con, &s.original_bills)? {
+            if confirm_first_bill(con, &s.original_bills, bill_idx)? {
+                s.create_output(con, s.original_bills[bill_idx..].to_vec(), AuditOperation::Resume)?;
+            }
+        }
+        Ok(MenuOutcome::Home)
+    });
+    extract_menu.add_action("2", "Skip from specified bill to end", |s: &mut Session, con: &mut Console| {
+        if let Some(bill_idx) = select_bill(con, &s.original_bills, "Account ID of last bill to keep (for example, 01-0123456-0): ")? {
+            s.create_output(con, s.original_bills[..=bill_idx].to_vec(), AuditOperation::Truncate)?;
+        }
+        Ok(MenuOutcome::Home)
+    });
+    extract_menu.add_action("3", "Extract range of bills", |s: &mut Session, con: &mut Console| {
+        extract_range(s, con)?;
+        Ok(MenuOutcome::Home)
+    });
+    extract_menu.add_section("One bill");
+    extract_menu.add_action("4", "Extract single bill", |s: &mut Session, con: &mut Console| {
+        if let Some(bill_idx) = select_bill(con, &s.original_bills, "Account ID of bill (for example, 01-0123456-0): ")? {
+            s.create_output(con, s.original_bills[bill_idx..=bill_idx].to_vec(), AuditOperation::Single)?;
+        }
+        Ok(MenuOutcome::Home)
+    });
+    extract_menu.add_action("5", "Find bill by name/address", |s: &mut Session, con: &mut Console| {
+        extract_found_bill(s, con)?;
+        Ok(MenuOutcome::Home)
+    });
+    extract_menu.add_action("6", "Pick bills from a list", |s: &mut Session, con: &mut Console| {
+        extract_picked_bills(s, con)?;
+        Ok(MenuOutcome::Home)
+    });
+
+    let mut print_menu = MMenu::titled("Printing");
+    print_menu.show_disabled(true);
+    print_menu.add_action("1", "Send processed file to printer", |s: &mut Session, con: &mut Console| {
+        print_output_file(s, con)?;
+        s.refresh_interrupted_job();
+        Ok(MenuOutcome::Home)
+    }).enabled_when(|s| s.file_ready() && s.output_filename.is_file())
+        .unavailable_because("make a processed file first");
+    print_menu.add_action("2", "Resume last interrupted print job", |s: &mut Session, con: &mut Console| {
+        resume_print_job(s, con)?;
+        s.refresh_interrupted_job();
+        Ok(MenuOutcome::Home)
+    }).enabled_when(Session::has_interrupted_job)
+        .unavailable_because("no print job was interrupted");
+
+    let mut main_menu = MMenu::titled("Main Menu");
+    main_menu.show_disabled(true);
+    main_menu.add_action("1", "Select bill file", |s: &mut Session, con: &mut Console| {
+        select_file(s, con)?;
+        Ok(MenuOutcome::Stay)
+    }).label_with(|s| match &s.loaded_path {
+        Some(filepath) => file_label(filepath, &s.original_bills),
+        None => "Select bill file".to_string(),
+    });
+    main_menu.add_submenu("2", "Extract bills", extract_menu).enabled_when(Session::file_ready)
+        .unavailable_because("load a bill file first");
+    main_menu.add_action("3", "Replace bulletin message on all bills", |s: &mut Session, con: &mut Console| {
+        if let Some(edited) = replace_bulletin(con, &s.original_bills)? {
+            s.create_output(con, edited.into_bills(), AuditOperation::Bulletin)?;
+        }
+        Ok(MenuOutcome::Stay)
+    }).enabled_when(Session::file_ready)
+        .unavailable_because("load a bill file first");
+    main_menu.add_submenu("4", "Printing", print_menu)
+        .enabled_when(|s| (s.file_ready() && s.output_filename.is_file()) || s.has_interrupted_job())
+        .unavailable_because("nothing to print yet");
+    main_menu.add_action("5", "Browse bills", browse_bills)
+        .enabled_when(Session::file_ready)
+        .unavailable_because("load a bill file first");
+    // a large bill file takes a while to load again
+    main_menu.confirm_quit_when("A bill file is loaded. Quit anyway? (y/n): ", Session::file_ready);
+    main_menu
+}
+
+/// Ask for a bill file and load it
+fn select_file(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    let file_choice = if session.file_dialog {
+        choose_file_in_dialog(con)?
+    } else {
+        Prompt::new("Enter path to file (press enter to cancel): ").cancel_on_empty().text(con)?.map(PathBuf::from)
+    };
+
+    if let Some(filepath) = file_choice {
+        writeln!(con, "Loading file (this may take a little while)")?;
+        if let Ok(ob) = get_file(con, &filepath) {
+            writeln!(con, "{} bills found", ob.get_bill_count())?;
+            session.load(&filepath, ob);
+        }
+    }
+    else {
+        session.loaded_path = None;
+        writeln!(con, "No luck getting file, returning to main menu.")?;
+    }
+    Ok(())
+}
+
+/// Ask for a bill file in the desktop's file dialog
+#[cfg(feature="native-ui")]
+fn choose_file_in_dialog(con: &mut Console) -> Result<Option<PathBuf>, DuckError> {
+    writeln!(con, "Use the dialog to select a file.")?;
+    let dialog_sel = FileDialog::new()
+            .set_location("~")
+            .show_open_single_file();
+    if let Ok(maybe_path) = dialog_sel {
+        Ok(maybe_path)
+    }
+    else {
+        writeln!(con, "No filename given, returning to menu.")?;
+        Ok(None)
+    }
+}
+
+/// Without native-ui there's no dialog to show, so nothing is chosen
+#[cfg(not(feature="native-ui"))]
+fn choose_file_in_dialog(_con: &mut Console) -> Result<Option<PathBuf>, DuckError> {
+    Ok(None)
+}
+
+/// Ask for the first and last accounts of a range and extract the bills between them
+fn extract_range(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    let bills = &session.original_bills;
+    let start_idx = select_bill(con, bills, "Account ID of starting bill (for example, 01-0123456-0): ")?;
+    let end_idx = match start_idx {
+        Some(_) => select_bill(con, bills, "Account ID of ending bill (for example, 01-0123456-0): ")?,
+        None => None
+    };
+
+    if let (Some(start_idx), Some(end_idx)) = (start_idx, end_idx) {
+        extract_between(session, con, start_idx, end_idx)?;
+    }
+    Ok(())
+}
 
-                if let Some(filepath) = file_choice {
-                    println!("Loading file (this may take a little while)");
-                    if let Ok(ob) = get_file(&filepath) {
-                        original_bills = ob;
-                        file_ready = true;
-                        println!("{} bills found", original_bills.get_bill_count());
-                        main_menu.add_entry("1", &format!("Select bill file (Current: {}, {} bills)", filepath.to_str().unwrap_or("Non-displayable file!"), original_bills.get_bill_count()), true);
-
-                        let mut outfilestr = filepath.into_os_string();
-                        outfilestr.push(".DUCKED");//append our signature extension
-                        output_filename = PathBuf::from(outfilestr);
+/// Extract the bills from one bill to another, swapping them if the end comes first
+fn extract_between(session: &mut Session, con: &mut Console, start_idx: usize, end_idx: usize) -> Result<(), DuckError> {
+    let (start, end) = if start_idx > end_idx {
+        writeln!(con, "End is before start, swapping...")?;
+        (end_idx, start_idx)
+    }
+    else {
+        (start_idx, end_idx)
+    };
+    let sel = session.original_bills[start..=end].to_vec();
+    session.create_output(con, sel, AuditOperation::Range)
+}
+
+/// Find a bill by name or address, then extract from, up to, or just that bill
+fn extract_found_bill(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    if let Some(bill_idx) = find_bill_by_text(con, &session.original_bills)? {
+        extract_around_bill(session, con, bill_idx)?;
+    }
+    Ok(())
+}
+
+/// Pick bills from a list of all the loaded bills. One bill can start, end or be the
+/// only bill kept; several can be kept on their own or as the range they span.
+fn extract_picked_bills(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    let bills = &session.original_bills;
+    let rows: Vec<String> = bills[..].iter().map(bill_row).collect();
+    let Some(picked) = Picker::new("Bills").multi_select().pick(con, &rows)? else {
+        writeln!(con, "No bills picked, returning to main menu.")?;
+        return Ok(());
+    };
+
+    match picked[..] {
+        [bill_idx] => extract_around_bill(session, con, bill_idx)?,
+        [first, .., last] => {
+            let question = format!("Keep the (r)ange from the first to the last picked bill, or (o)nly the {} picked bills (press enter to cancel): ", picked.len());
+            match Prompt::new(&question).cancel_on_empty().ask(con, letter_of(&["r", "o"]))? {
+                Some("r") => session.create_output(con, bills[first..=last].to_vec(), AuditOperation::Range)?,
+                Some(_) => session.create_output(con, picked.iter().map(|&i| bills[i].clone()).collect(), AuditOperation::Picked)?,
+                None => writeln!(con, "No output created, returning to main menu.")?
+            }
+        }
+        [] => {}
+    }
+    Ok(())
+}
+
+/// Ask whether a bill starts, ends or is the only bill to keep, and extract accordingly
+fn extract_around_bill(session: &mut Session, con: &mut Console, bill_idx: usize) -> Result<(), DuckError> {
+    let how = Prompt::new("Use this bill as (s)tart, (e)nd, or (o)nly bill to keep (press enter to cancel): ")
+        .cancel_on_empty().ask(con, letter_of(&["s", "e", "o"]))?;
+
+    let bills = &session.original_bills;
+    match how {
+        Some("s") => session.create_output(con, bills[bill_idx..].to_vec(), AuditOperation::Resume)?,
+        Some("e") => session.create_output(con, bills[..=bill_idx].to_vec(), AuditOperation::Truncate)?,
+        Some(_) => session.create_output(con, bills[bill_idx..=bill_idx].to_vec(), AuditOperation::Single)?,
+        None => writeln!(con, "No output created, returning to main menu.")?
+    }
+    Ok(())
+}
+
+/// List the loaded bills and show the details of any that are picked, so the operator
+/// can check they have the right customer. From a bill's details it can be extracted
+/// on its own or marked as the start or end of a range.
+fn browse_bills(session: &mut Session, con: &mut Console) -> Result<MenuOutcome, DuckError> {
+    loop {
+        let rows: Vec<String> = session.original_bills[..].iter().enumerate().map(|(i, bill)| {
+            let mark = if session.range_start == Some(i) { "  <- start" } else if session.range_end == Some(i) { "  <- end" } else { "" };
+            format!("{}{}", bill_row(bill), mark)
+        }).collect();
+        let Some(bill_idx) = Picker::new("Bills").pick_one(con, &rows)? else {
+            return Ok(MenuOutcome::Stay);
+        };
+
+        show_bill_details(con, &session.original_bills, bill_idx)?;
+        loop {
+            let how = Prompt::new("(v)iew as text, extract (o)nly this bill, mark as range (s)tart or (e)nd, or press enter for the list: ")
+                .cancel_on_empty().ask(con, letter_of(&["v", "o", "s", "e"]))?;
+            match how {
+                Some("v") => {
+                    for line in session.original_bills[bill_idx].get_plain_text() {
+                        writeln!(con, "| {}", line)?;
                     }
+                    continue;
                 }
-                else {
-                    file_ready = false;
-                    main_menu.add_entry("1", "Select bill file", true);
-                    println!("No luck getting file, returning to main menu.");
-                }
-            },
-            "2" => {
-                let s = get_acct_id("Account ID of first bill to keep (for example, 01-0123456-0): ")?;
-                if let Some(sel) = original_bills.get_index_of_account(&s).map(|bill_idx| &original_bills[bill_idx..]) {
-                    create_output_file(&output_filename, sel.to_vec())?;
+                Some("o") => {
+                    let sel = session.original_bills[bill_idx..=bill_idx].to_vec();
+                    session.create_output(con, sel, AuditOperation::Single)?;
+                    return Ok(MenuOutcome::Stay);
                 }
-                else {
-                    println!("Account ID not valid!");
-                }
-            },
-            "3" => {
-                let s = get_acct_id("Account ID of last bill to keep (for example, 01-0123456-0): ")?;
-                if let Some(sel) = original_bills.get_index_of_account(&s).map(|bill_idx| &original_bills[..=bill_idx]) {
-                    create_output_file(&output_filename, sel.to_vec())?;
-                }
-                else {
-                    println!("Account ID not valid!");
-                }
-            },
-            "4" => {
-                let s = get_acct_id("Account ID of starting bill (for example, 01-0123456-0): ")?;
-                let e = get_acct_id("Account ID of ending bill (for example, 01-0123456-0): ")?;
-
-                let start_idx = original_bills.get_index_of_account(&s);
-                let end_idx = original_bills.get_index_of_account(&e);
-
-                if start_idx.is_some() && end_idx.is_some() {
-                    let (start, end) = if start_idx.unwrap() > end_idx.unwrap() {
-                        println!("End is before start, swapping...");
-                        (end_idx.unwrap(), start_idx.unwrap())
+                Some("s") => session.range_start = Some(bill_idx),
+                Some("e") => session.range_end = Some(bill_idx),
+                _ => {}
+            }
+            break;
+        }
+
+        if let (Some(start), Some(end)) = (session.range_start, session.range_end) {
+            let question = format!("Extract bills {} to {} now? (y/n): ", start + 1, end + 1);
+            if Prompt::new(&question).cancel_on_empty().yes_no(con)? == Some(true) {
+                extract_between(session, con, start, end)?;
+                session.range_start = None;
+                session.range_end = None;
+                return Ok(MenuOutcome::Stay);
+            }
+        }
+    }
+}
+
+/// Show what can be read from a bill: its fields, meter readings and size
+fn show_bill_details(con: &mut Console, bills: &DuckFile, bill_idx: usize) -> Result<(), DuckError> {
+    let bill = &bills[bill_idx];
+    writeln!(con, "\nBill {} of {}", bill_idx + 1, bills.get_bill_count())?;
+    writeln!(con, "  Account ID:       {}", bill.get_account_id())?;
+    writeln!(con, "  Bill number:      {}", bill.get_bill_number())?;
+    writeln!(con, "  Name:             {}", bill.get_name().unwrap_or_default())?;
+    writeln!(con, "  Service address:  {}", bill.get_service_address().join(", "))?;
+    writeln!(con, "  Mailing address:  {}", bill.get_mailing_address().join(", "))?;
+    writeln!(con, "  Amount due:       {}", bill.get_amount_due().map(format_amount).unwrap_or_default())?;
+    writeln!(con, "  Size:             {} bytes", bill.get_raw().len())?;
+    let readings = bill.get_meter_readings();
+    if readings.is_empty() {
+        writeln!(con, "  No meter readings")?;
+    }
+    else {
+        writeln!(con, "  Meter readings:")?;
+        for reading in readings {
+            writeln!(con, "  {}", reading.to_line())?;
+        }
+    }
+    Ok(())
+}
+
+/// Load the file of the last interrupted print job and make a file of the bills it didn't send
+fn resume_print_job(session: &mut Session, con: &mut Console) -> Result<(), DuckError> {
+    let Some(checkpoint) = session.interrupted_job.clone() else { return Ok(()) };
+    let filepath = checkpoint.get_printed_file().to_path_buf();
+    writeln!(con, "The last interrupted job was printing {}", filepath.display())?;
+    let bills = match get_file(con, &filepath) {
+        Ok(b) => b,
+        Err(_) => {
+            writeln!(con, "That file could not be loaded, so the job can't be resumed.")?;
+            return Ok(());
+        }
+    };
+    let Ok(resume_idx) = checkpoint.get_resume_index(&bills) else {
+        writeln!(con, "That file has changed since it was printed, so the job can't be resumed.")?;
+        return Ok(());
+    };
+    match checkpoint.get_last_sent() {
+        Some((acct, bill_number)) => {
+            writeln!(con, "The last bill handed to the printer was account {}, bill #{}.", acct, bill_number)?;
+            writeln!(con, "The last few bills before it may not have reached the printer when the job failed;")?;
+            writeln!(con, "check them against what was printed, and extract from an earlier bill if any are missing.")?;
+        }
+        None => writeln!(con, "No bills were handed to the printer.")?,
+    }
+    writeln!(con, "{} of {} bills are left to print.", checkpoint.get_bill_count() - resume_idx.min(checkpoint.get_bill_count()), checkpoint.get_bill_count())?;
+    if resume_idx >= bills.get_bill_count() {
+        writeln!(con, "Every bill was sent; there is nothing left to print.")?;
+        checkpoint.mark_resumed()?;
+        return Ok(());
+    }
+
+    session.load(&filepath, bills);
+    if confirm_first_bill(con, &session.original_bills, resume_idx)? {
+        session.create_output(con, session.original_bills[resume_idx..].to_vec(), AuditOperation::Resume)?;
+        checkpoint.mark_resumed()?;
+    }
+    Ok(())
+}
+
+/// Check the audit log's hash chain, report the result, and exit
+fn verify_audit_log(log_path: &Path) -> ! {
+    let log = match std::fs::read_to_string(log_path) {
+        Ok(log) => log,
+        Err(_) => {
+            eprintln!("Could not read the audit log {}", log_path.display());
+            std::process::exit(2);
+        }
+    };
+    match audit::verify(&log) {
+        Ok(count) => {
+            println!("Audit log {} is intact ({} entries).", log_path.display(), count);
+            std::process::exit(0);
+        }
+        Err(fault) => {
+            println!("Audit log {} has been altered at {}.", log_path.display(), fault);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Identify a loaded bill file for the audit log
+fn audit_source(filepath: &Path, bills: &DuckFile) -> AuditSource {
+    AuditSource {
+        path: std::fs::canonicalize(filepath).unwrap_or(filepath.to_path_buf()),
+        sha256: checkpoint::file_hash(bills),
+    }
+}
+
+/// Add an entry to the audit log, warning the operator if it can't be written
+fn record_audit(con: &mut Console, log_path: &Path, entry: &AuditEntry) {
+    if audit::append(log_path, entry).is_err() {
+        let _ = writeln!(con, "WARNING: this operation could not be recorded in the audit log {}", log_path.display());
+    }
+}
+
+/// Describe the loaded bill file for the main menu
+fn file_label(filepath: &Path, bills: &DuckFile) -> String {
+    format!("Select bill file (Current: {}, {} bills, {} pages, {} sheets)",
+            filepath.to_str().unwrap_or("Non-displayable file!"), bills.get_bill_count(), bills.page_count(), bills.sheet_count())
+}
+
+/// How many near matches to offer when an account ID isn't found
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Ask for an account ID and find its bill.
+/// IDs may be typed with or without dashes or spaces. If no bill matches exactly,
+/// the closest accounts are offered so the operator can pick one.
+/// Returns None if the operator cancels or nothing suitable is found.
+fn select_bill(con: &mut Console, bills: &DuckFile, prompt: &str) -> Result<Option<usize>, DuckError> {
+    let Some(id) = Prompt::new(prompt).cancel_on_empty().text(con)? else {
+        writeln!(con, "No account entered, returning to main menu.")?;
+        return Ok(None);
+    };
+
+    if let Ok(acct) = DuckAcctId::normalize(&id) {
+        if let Some(bill_idx) = bills.get_index_of_account(&acct) {
+            return Ok(Some(bill_idx));
+        }
+    }
+
+    let suggestions = bills.suggest_accounts(&id, MAX_SUGGESTIONS);
+    if suggestions.is_empty() {
+        writeln!(con, "Account ID not valid!")?;
+        return Ok(None);
+    }
+
+    writeln!(con, "Account ID not found. Did you mean:")?;
+    let choices: Vec<String> = suggestions.iter().map(|s| {
+        let bill = &bills[s.get_index()];
+        format!("{}  {:<30} Bill #{}", bill.get_account_id(), bill.get_name().unwrap_or_default(), bill.get_bill_number())
+    }).collect();
+
+    match Prompt::new("Select number (press enter to cancel): ").cancel_on_empty().choose(con, &choices)? {
+        Some(n) => Ok(Some(suggestions[n].get_index())),
+        None => {
+            writeln!(con, "No bill selected, returning to main menu.")?;
+            Ok(None)
+        }
+    }
+}
+
+/// One line describing a bill in a list: account, name, amount due and bill number
+fn bill_row(bill: &DuckBill) -> String {
+    format!("{}  {:<30} {:>11}  Bill #{}", bill.get_account_id(), bill.get_name().unwrap_or_default(),
+            bill.get_amount_due().map(format_amount).unwrap_or_default(), bill.get_bill_number())
+}
+
+/// Ask how to find the bill a print job should resume from, then find it.
+/// Jobs can resume from an account ID, the printer's page count, the bill number
+/// printed on the stub, or a bill's position in the file.
+fn select_resume_bill(con: &mut Console, bills: &DuckFile) -> Result<Option<usize>, DuckError> {
+    writeln!(con, "Resume from:")?;
+    writeln!(con, "  (a) Account ID of first bill to keep")?;
+    writeln!(con, "  (p) Page count shown by the printer")?;
+    writeln!(con, "  (b) Bill number of first bill to keep")?;
+    writeln!(con, "  (n) Position of first bill to keep in the file (1 for the first bill)")?;
+    let Some(how) = Prompt::new("Choose how: ").default("a").ask(con, letter_of(&["a", "p", "b", "n"]))? else {
+        return Ok(None);
+    };
+
+    match how {
+        "a" => select_bill(con, bills, "Account ID of first bill to keep (for example, 01-0123456-0): "),
+        "p" => {
+            let map = bills.page_map();
+            let Some(printed) = Prompt::new("Number of pages printed successfully: ").cancel_on_empty().number(con, 0..=map.get_page_count())? else {
+                writeln!(con, "Nothing entered, returning to main menu.")?;
+                return Ok(None);
+            };
+            match map.get_bill_of_page(printed) {
+                Some(bill_idx) => {
+                    if map.get_pages_of_bill(bill_idx).is_some_and(|pages| pages.start < printed) {
+                        writeln!(con, "Page {} is partway through a bill; that whole bill will be printed again.", printed + 1)?;
                     }
-                    else {
-                        (start_idx.unwrap(), end_idx.unwrap())
-                    };
-                    let sel = &original_bills[start..=end];
-                    create_output_file(&output_filename, sel.to_vec())?;
+                    Ok(Some(bill_idx))
                 }
-                else {
-                    println!("Account ID not valid!");
+                None => {
+                    writeln!(con, "This file only has {} pages, so every page has been printed.", map.get_page_count())?;
+                    Ok(None)
                 }
             }
-            "5" => {
-                let s = get_acct_id("Account ID of bill (for example, 01-0123456-0): ")?;
-                if let Some(sel) = original_bills.get_index_of_account(&s).map(|bill_idx| &original_bills[bill_idx..=bill_idx]) {
-                    create_output_file(&output_filename, sel.to_vec())?;
-                }
-                else {
-                    println!("Account ID not valid!");
+        }
+        "b" => {
+            let bill_number = Prompt::new("Bill number of first bill to keep: ").cancel_on_empty()
+                .ask(con, |answer| answer.parse::<u32>().map_err(|_| "Enter the bill number printed on the bill.".to_string()))?;
+            let Some(bill_number) = bill_number else {
+                writeln!(con, "Nothing entered, returning to main menu.")?;
+                return Ok(None);
+            };
+            let found = bills.get_index_of_bill_number(bill_number);
+            if found.is_none() {
+                writeln!(con, "No bill has that number.")?;
+            }
+            Ok(found)
+        }
+        "n" => {
+            let prompt = Prompt::new(&format!("Position of first bill to keep (1 to {}): ", bills.get_bill_count())).cancel_on_empty();
+            match prompt.number(con, 1..=bills.get_bill_count())? {
+                Some(position) => Ok(Some(position - 1)),
+                None => {
+                    writeln!(con, "Nothing entered, returning to main menu.")?;
+                    Ok(None)
                 }
-            } ,
+            }
+        }
+        _ => {
+            writeln!(con, "Not a choice, returning to main menu.")?;
+            Ok(None)
+        }
+    }
+}
 
-            _ => {println!("Well, this should be impossible.");}
+/// Show the bill that will be printed first and ask the operator to confirm it
+fn confirm_first_bill(con: &mut Console, bills: &DuckFile, bill_idx: usize) -> Result<bool, DuckError> {
+    let bill = &bills[bill_idx];
+    let map = bills.page_map();
+    writeln!(con, "The first bill printed will be:")?;
+    writeln!(con, "  Bill {} of {} in the file, starting on page {}",
+             bill_idx + 1,
+             bills.get_bill_count(),
+             map.get_pages_of_bill(bill_idx).map(|p| p.start + 1).unwrap_or(0))?;
+    writeln!(con, "  Account {}  Bill #{}", bill.get_account_id(), bill.get_bill_number())?;
+    writeln!(con, "  {}", bill.get_name().unwrap_or_default())?;
+    for line in bill.get_service_address() {
+        writeln!(con, "  {}", line)?;
+    }
+    writeln!(con, "{} bills ({} pages) will be kept.",
+             bills.get_bill_count() - bill_idx,
+             map.get_page_count() - map.get_pages_of_bill(bill_idx).map(|p| p.start).unwrap_or(0))?;
+    if Prompt::new("Is this right? (y/n): ").cancel_on_empty().yes_no(con)? == Some(true) {
+        Ok(true)
+    } else {
+        writeln!(con, "No output created, returning to main menu.")?;
+        Ok(false)
+    }
+}
+
+/// How many text search results to list
+const MAX_SEARCH_RESULTS: usize = 20;
+
+/// Ask for part of a customer name or address and let the operator pick a matching bill.
+/// Returns None if the operator cancels or nothing matches.
+fn find_bill_by_text(con: &mut Console, bills: &DuckFile) -> Result<Option<usize>, DuckError> {
+    let Some(search) = Prompt::new("Name or address to find (press enter to cancel): ").cancel_on_empty().text(con)? else {
+        writeln!(con, "Nothing entered, returning to main menu.")?;
+        return Ok(None);
+    };
+
+    let matches = bills.search_text(&search);
+    if matches.is_empty() {
+        writeln!(con, "No bills match that name or address.")?;
+        return Ok(None);
+    }
+
+    writeln!(con, "{} bills found{}:", matches.len(),
+             if matches.len() > MAX_SEARCH_RESULTS { format!(", showing best {}", MAX_SEARCH_RESULTS) } else { String::new() })?;
+    let choices: Vec<String> = matches.iter().take(MAX_SEARCH_RESULTS).map(|m| {
+        let bill = &bills[m.get_index()];
+        format!("{}  {:<30} {}", bill.get_account_id(), bill.get_name().unwrap_or_default(), bill.get_service_address().join(", "))
+    }).collect();
+
+    match Prompt::new("Select number (press enter to cancel): ").cancel_on_empty().choose(con, &choices)? {
+        Some(n) => Ok(Some(matches[n].get_index())),
+        None => {
+            writeln!(con, "No bill selected, returning to main menu.")?;
+            Ok(None)
         }
     }
 }
 
-fn get_acct_id(prompt: &str) -> Result<DuckAcctId, DuckError> {
-    loop {
-        print!("{}", prompt); io::stdout().flush()?;
-        let mut id = String::new();
-        std::io::stdin().read_line(&mut id)?;
-        // account ID format is 01-0123456-0
-        // plus one newline equals 13
-        if id.len() >= 13 {
-            let id_bytes = id.trim().as_bytes().to_owned();
-            break DuckAcctId::try_from(id_bytes);
+/// Ask for new bulletin text and apply it to a copy of the loaded bills.
+/// Returns the changed copy, or None if nothing was changed; the loaded bills are left as read.
+fn replace_bulletin(con: &mut Console, bills: &DuckFile) -> Result<Option<DuckFile>, DuckError> {
+    let Some(first) = bills[..].first() else {
+        writeln!(con, "There are no bills to change.")?;
+        return Ok(None);
+    };
+    let (current, widths) = match (first.get_bulletin(), first.get_bulletin_widths()) {
+        (Some(c), Some(w)) => (c, w),
+        _ => {
+            writeln!(con, "The first bill has no bulletin message to replace.")?;
+            return Ok(None);
         }
-        else if id.len() == 1 {
-            break Err(DuckError::OpCancelled);
+    };
+
+    writeln!(con, "Current bulletin message:")?;
+    for line in current.iter() {
+        writeln!(con, "  {}", line)?;
+    }
+    writeln!(con, "Enter the new message. Press enter to keep a line, or type - to blank it.")?;
+
+    let mut new_lines: Vec<String> = vec![];
+    for (n, line) in current.iter().enumerate() {
+        write!(con, "Line {} (up to {} characters): ", n + 1, widths[n])?;
+        let input = con.read_line()?.unwrap_or_default();
+        let input = input.trim_end_matches(['\r', '\n']);
+        new_lines.push(match input {
+            "" => line.clone(),
+            "-" => String::new(),
+            text => text.to_string(),
+        });
+    }
+
+    if new_lines == current {
+        writeln!(con, "Bulletin unchanged, returning to main menu.")?;
+        return Ok(None);
+    }
+
+    let lines: Vec<&str> = new_lines.iter().map(|l| l.as_str()).collect();
+    let mut edited = DuckFile::try_from(bills[..].to_vec())?;
+    let report = edited.replace_bulletin(&lines);
+    writeln!(con, "Bulletin replaced on {} bills.", report.get_replaced_count())?;
+    for &n in report.get_truncated_lines() {
+        writeln!(con, "Warning: line {} was too long and has been cut short.", n + 1)?;
+    }
+    if !report.get_missing().is_empty() {
+        writeln!(con, "The bulletin could not be found on these bills, which were left unchanged:")?;
+        for &idx in report.get_missing() {
+            writeln!(con, "  {}", bills[idx])?;
         }
     }
+    Ok((report.get_replaced_count() > 0).then_some(edited))
 }
 
+fn get_file(con: &mut Console, file_choice: &Path) -> Result <DuckFile, DuckError>{
+    let result = File::open(file_choice).map_err(DuckError::from).and_then(DuckFile::read_from);
+    match result {
+        Err(DuckError::FileTooSmall) => writeln!(con, "That file is too short to be valid.")?,
+        Err(DuckError::FileTooBig) => writeln!(con, "That file is too large to be valid.")?,
+        _ => {}
+    }
+    result
+}
+
+/// Ask where to print, then send the processed file there, showing progress bill by bill
+fn print_output_file(session: &Session, con: &mut Console) -> Result<(), DuckError> {
+    let filename = &session.output_filename;
+    let processed = match get_file(con, filename) {
+        Ok(p) => p,
+        Err(_) => {
+            writeln!(con, "The processed file could not be read, returning to main menu.")?;
+            return Ok(());
+        }
+    };
 
-fn get_file(file_choice: &PathBuf) -> Result <DuckFile, DuckError>{
-    let bill_file = File::open(file_choice);
+    writeln!(con, "Print to:")?;
+    writeln!(con, "  (t) Network printer (raw port {})", printer::RAW_PORT)?;
+    writeln!(con, "  (l) CUPS print queue (lp)")?;
+    writeln!(con, "  (s) Spool folder")?;
+    let how = Prompt::new("Choose a printer (press enter to cancel): ").cancel_on_empty().ask(con, letter_of(&["t", "l", "s"]))?;
 
-    match bill_file {
-        Err(_) => Err(DuckError::IoError),
-        Ok(mut bf) => {
-            match bf.metadata() {
-                Err(_) => Err(DuckError::IoError),
-                Ok(m) => {
-                    if m.len() < 4000 { // minimum length sanity check
-                        println!("That file is too short to be valid.");
-                        Err(DuckError::FileTooSmall)
-                    }
-                    else if m.len() > 100_000_000 {// maximum length sanity check
-                        println!("That file is too large to be valid.");
-                        Err(DuckError::FileTooBig)
-                    }
-                    else {
-                        let mut bill_data = Vec::with_capacity(m.len() as usize);
-                        match bf.read_to_end(&mut bill_data) {
-                            Err(_) => Err(DuckError::IoError),
-                            Ok(_) => DuckFile::try_from(DuckData::from(bill_data))
-                        }
-                    }
-                }
+    let mut backend: Box<dyn PrintBackend> = match how {
+        Some("t") => {
+            let Some(address) = Prompt::new("Printer address (host or host:port): ").cancel_on_empty().text(con)? else {
+                writeln!(con, "No address entered, returning to main menu.")?;
+                return Ok(());
+            };
+            Box::new(RawTcpPrinter::new(&address))
+        }
+        Some("l") => {
+            let queue = Prompt::new("Print queue (press enter for the default queue): ").cancel_on_empty().text(con)?;
+            Box::new(LpPrinter::new(queue.as_deref()))
+        }
+        Some(_) => {
+            let Some(dir) = Prompt::new("Spool folder: ").cancel_on_empty().text(con)? else {
+                writeln!(con, "No folder entered, returning to main menu.")?;
+                return Ok(());
+            };
+            let job_name = filename.file_name().and_then(|n| n.to_str()).unwrap_or("bills.DUCKED");
+            Box::new(SpoolDirPrinter::new(PathBuf::from(dir), job_name))
+        }
+        None => {
+            writeln!(con, "Nothing printed, returning to main menu.")?;
+            return Ok(());
+        }
+    };
+
+    let mut journal = match CheckpointJournal::start(&session.checkpoint_dir, filename, &processed) {
+        Ok(j) => Some(j),
+        Err(_) => {
+            writeln!(con, "Warning: the checkpoint journal could not be started; an interrupted job will not resume automatically.")?;
+            None
+        }
+    };
+
+    writeln!(con, "Sending {} bills to {}", processed.get_bill_count(), backend.describe())?;
+    let mut sent = 0;
+    let result = backend.print(&processed, &mut |p| {
+        sent = p.bills_sent;
+        if let Some(j) = journal.as_mut() {
+            if j.record(p.bills_sent - 1, &processed[p.bills_sent - 1]).is_err() {
+                journal = None;
+            }
+        }
+        let _ = write!(con, "\rSent bill {} of {} ({}%)", p.bills_sent, p.bill_count, p.bytes_sent * 100 / p.byte_count.max(1));
+        let _ = con.flush();
+    });
+    writeln!(con)?;
+    // bills reached the printer even if the job failed partway
+    record_audit(con, &session.audit_log, &AuditEntry {
+        operation: AuditOperation::Print,
+        source: &audit_source(filename, &processed),
+        bills: &processed[..sent],
+        output: &backend.describe(),
+        output_sha256: &checkpoint::file_hash(&processed),
+    });
+    match result {
+        Ok(()) => {
+            writeln!(con, "All bills sent.")?;
+            if let Some(j) = journal {
+                j.finish()?;
             }
         }
+        Err(_) => writeln!(con, "Printing failed; check the printer, then use \"Resume last interrupted print job\".")?,
     }
+    Ok(())
 }
 
-fn create_output_file(filename: &PathBuf, bill_sel: Vec<DuckBill>) -> Result<(), DuckError>{
-    let mut o = File::create(&filename)?;
+/// Ask whether the output bills should be stamped, and with what
+fn choose_overlay(con: &mut Console) -> Result<Option<DuckOverlay>, DuckError> {
+    let reprint = DuckOverlay::reprint(&clock::today_short());
+    writeln!(con, "Stamp the output bills?")?;
+    writeln!(con, "  (d) {}", DuckOverlay::duplicate())?;
+    writeln!(con, "  (r) {}", reprint)?;
+    writeln!(con, "  (c) Custom text")?;
+    let choice = Prompt::new("Choose a stamp, or press enter for none: ").cancel_on_empty().ask(con, letter_of(&["d", "r", "c"]))?;
 
-    let processed_file: DuckFile = bill_sel.try_into()?;
-    let new_data: DuckData = processed_file.into();
-    if o.write(new_data.as_ref()).is_ok() {
-        println!("Working...");
-        println!();
-        println!("Your processed file is ready: {}", filename.to_str().unwrap_or("Undisplayable filename. Nice work."));
-        return Ok(())
+    match choice {
+        Some("d") => Ok(Some(DuckOverlay::duplicate())),
+        Some("r") => Ok(Some(reprint)),
+        Some(_) => {
+            let text = Prompt::new("Stamp text: ").cancel_on_empty().ask(con, |text| match text.is_ascii() {
+                true => Ok(text.to_string()),
+                false => Err("The stamp can only print plain letters, digits and punctuation.".to_string()),
+            })?;
+            Ok(text.map(|t| DuckOverlay::custom(&t)))
+        }
+        None => Ok(None)
     }
-    else {
-        println!("An error occurred while writing the output file.");
+}
+
+/// A `Prompt::ask` validator that accepts one of the listed letters, in either case,
+/// so a mistyped letter is asked for again instead of cancelling
+fn letter_of(letters: &'static [&'static str]) -> impl Fn(&str) -> Result<&'static str, String> {
+    move |answer| {
+        let answer = answer.to_lowercase();
+        letters.iter().find(|&&l| l == answer).copied().ok_or_else(|| match letters {
+            [rest @ .., last] if !rest.is_empty() => format!("Enter {} or {}.", rest.join(", "), last),
+            _ => format!("Enter {}.", letters.join("")),
+        })
+    }
+}
+
+/// Write the selected bills to a new processed file named by the output policy,
+/// and remember where it went in `written`
+fn create_output_file(con: &mut Console, policy: &OutputPolicy, written: &mut PathBuf, bill_sel: Vec<DuckBill>, operation: AuditOperation, source: &AuditSource, audit_log: &Path) -> Result<(), DuckError>{
+    let mut processed_file: DuckFile = bill_sel.try_into()?;
+    if let Some(overlay) = choose_overlay(con)? {
+        processed_file = processed_file.with_overlay(&overlay)?;
+        writeln!(con, "Stamping {} bill(s) with \"{}\".", processed_file.get_bill_count(), overlay)?;
+    }
+
+    writeln!(con, "Working...")?;
+    let filename = policy.name_for(&source.path, operation, &processed_file);
+    match policy.write(&filename, &processed_file) {
+        Ok(output) => {
+            let filename = output.path;
+            record_audit(con, audit_log, &AuditEntry {
+                operation,
+                source,
+                bills: &processed_file[..],
+                output: &filename.display().to_string(),
+                output_sha256: &output.sha256,
+            });
+            writeln!(con)?;
+            writeln!(con, "Your processed file is ready: {}", filename.to_str().unwrap_or("Undisplayable filename. Nice work."))?;
+            *written = filename;
+        }
+        Err(_) => writeln!(con, "An error occurred while writing the output file; nothing was saved.")?,
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    /// Copy the sample bill file into a fresh scratch folder
+    fn scratch_session(name: &str) -> (PathBuf, Session) {
+        let dir = std::env::temp_dir().join(format!("justducky-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("../duckbill/test_data/anon_bill_file_4.dat");
+        std::fs::copy(sample, dir.join("april.dat")).unwrap();
+        let session = Session::new(OutputPolicy::default(), dir.join("audit.log"), dir.join("checkpoints"));
+        (dir, session)
+    }
+
+    /// Play a scripted session, returning the transcript with the scratch folder shown as <dir>
+    fn play(dir: &Path, session: &mut Session, script: &str) -> String {
+        let mut shown = vec![];
+        {
+            let mut con = Console::new(script.as_bytes(), &mut shown).echo_input();
+            run_session(session, &mut con).unwrap();
+        }
+        String::from_utf8(shown).unwrap()
+            .replace(&dir.display().to_string(), "<dir>")
+            .replace(&clock::today_short(), "<today>")
+    }
+
+    #[test]
+    fn extract_single_bill() {
+        let (dir, mut session) = scratch_session("session-single");
+        let script = format!("1\n{}\n2\n4\n52-3333333-1\n\nq\ny\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+        assert_eq!(transcript, "Welcome to the Just Ducky Second Chance Bill Handler
+====================================================
+
+Main Menu
+=========
+1 Select bill file
+2 Extract bills (load a bill file first)
+3 Replace bulletin message on all bills (load a bill file first)
+4 Printing (nothing to print yet)
+5 Browse bills (load a bill file first)
+q Quit
+Select an option: 1
+
+Enter path to file (press enter to cancel): <dir>/april.dat
+Loading file (this may take a little while)
+4 bills found
+
+Main Menu
+=========
+1 Select bill file (Current: <dir>/april.dat, 4 bills, 4 pages, 4 sheets)
+2 Extract bills
+3 Replace bulletin message on all bills
+4 Printing (nothing to print yet)
+5 Browse bills
+q Quit
+Select an option: 2
+
+
+Extract Bills
+=============
+Several bills
+1 Skip from start to specified bill (resume print job)
+2 Skip from specified bill to end
+3 Extract range of bills
+
+One bill
+4 Extract single bill
+5 Find bill by name/address
+6 Pick bills from a list
+b Back
+h Main menu
+q Quit
+Select an option: 4
+
+Account ID of bill (for example, 01-0123456-0): 52-3333333-1
+Stamp the output bills?
+  (d) DUPLICATE
+  (r) REPRINT <today>
+  (c) Custom text
+Choose a stamp, or press enter for none: 
+Working...
+
+Your processed file is ready: <dir>/april.dat.DUCKED
+
+Main Menu
+=========
+1 Select bill file (Current: <dir>/april.dat, 4 bills, 4 pages, 4 sheets)
+2 Extract bills
+3 Replace bulletin message on all bills
+4 Printing
+5 Browse bills
+q Quit
+Select an option: q
+
+A bill file is loaded. Quit anyway? (y/n) [n]: y
+Bye!
+");
+
+        let output = get_file(&mut Console::new(io::empty(), io::sink()), &dir.join("april.dat.DUCKED")).unwrap();
+        assert_eq!(output.get_bill_count(), 1);
+        assert_eq!(output[0].get_account_id().to_string(), "52-3333333-1");
+        assert_eq!(audit::verify(&std::fs::read_to_string(dir.join("audit.log")).unwrap()), Ok(1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bulletin_changes_only_the_output() {
+        let (dir, mut session) = scratch_session("session-bulletin");
+        let script = format!("1\n{}\n3\nPAY ONLINE\n\n\n\n\n\n\n\n\nq\ny\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+        assert!(transcript.contains("Bulletin replaced on 4 bills.\n"));
+
+        let output = get_file(&mut Console::new(io::empty(), io::sink()), &dir.join("april.dat.DUCKED")).unwrap();
+        assert_eq!(output[2].get_bulletin().unwrap()[0].trim_end(), "PAY ONLINE");
+        // the loaded bills still match the file they were read from
+        assert_ne!(session.original_bills[2].get_bulletin().unwrap()[0].trim_end(), "PAY ONLINE");
+        assert_eq!(checkpoint::file_hash(&session.original_bills), session.source.sha256);
+
+        let mut shown = vec![];
+        assert!(replace_bulletin(&mut Console::new(io::empty(), &mut shown), &DuckFile::new()).unwrap().is_none());
+        assert_eq!(shown, b"There are no bills to change.\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pick_bills_from_a_list() {
+        let (dir, mut session) = scratch_session("session-pick");
+        let script = format!("1\n{}\n2\n6\n/name\n4\n2\nd\no\n\nq\ny\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+        assert!(transcript.contains("\nBills 1-4 of 4, page 1 of 1\n\
+                                     \x20 1) 52-1111111-1  NAME NUMBER 11                     $145.21  Bill #7488\n"));
+        assert!(transcript.contains("\nBills 1-4 of 4 containing \"name\", page 1 of 1\n"));
+        assert!(transcript.contains("* 4) 52-4444444-1"));
+        assert!(transcript.contains("Pick items, d when done (2 picked): d\n\
+                                     Keep the (r)ange from the first to the last picked bill, or (o)nly the 2 picked bills (press enter to cancel): o\n"));
+
+        let output = get_file(&mut Console::new(io::empty(), io::sink()), &dir.join("april.dat.DUCKED")).unwrap();
+        let kept: Vec<String> = output[..].iter().map(|b| b.get_account_id().to_string()).collect();
+        assert_eq!(kept, ["52-2222222-1", "52-4444444-1"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn browse_and_mark_a_range() {
+        let (dir, mut session) = scratch_session("session-browse");
+        // look at the third bill as text, mark it as the end, then mark the second as the start
+        let script = format!("1\n{}\n5\n3\nv\ne\n2\ns\ny\n\nq\ny\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+        assert!(transcript.contains("Bill 3 of 4\n  Account ID:       52-3333333-1\n  Bill number:      9648\n\
+                                     \x20 Name:             NAME THREE THREE33\n"));
+        assert!(transcript.contains("  Amount due:       $2.36\n"));
+        assert!(transcript.contains("  Meter readings:\n     001 "));
+        assert!(transcript.contains("| ") && transcript.contains("Account #:       52-3333333-1"));
+        assert!(transcript.contains("3) 52-3333333-1  NAME THREE THREE33                   $2.36  Bill #9648  <- end\n"));
+        assert!(transcript.contains("Extract bills 2 to 3 now? (y/n): y\n"));
+
+        let output = get_file(&mut Console::new(io::empty(), io::sink()), &dir.join("april.dat.DUCKED")).unwrap();
+        let kept: Vec<String> = output[..].iter().map(|b| b.get_account_id().to_string()).collect();
+        assert_eq!(kept, ["52-2222222-1", "52-3333333-1"]);
+        assert_eq!((session.range_start, session.range_end), (None, None));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resume_then_print_to_spool() {
+        let (dir, mut session) = scratch_session("session-print");
+        let spool = dir.join("spool");
+        std::fs::create_dir(&spool).unwrap();
+        // resume from the 2nd bill, mistyping the position and each letter once, then print to a spool folder
+        let script = format!("1\n{}\n2\n1\nx\nn\n7\n2\ny\nz\nd\n4\n1\nw\ns\n{}\nq\ny\n",
+                             dir.join("april.dat").display(), spool.display());
+        let transcript = play(&dir, &mut session, &script);
+        assert!(transcript.contains("Choose how [a]: x\nEnter a, p, b or n.\nChoose how [a]: n\n"));
+        assert!(transcript.contains("Position of first bill to keep (1 to 4): 7\nEnter a number from 1 to 4.\n"));
+        assert!(transcript.contains("Choose a stamp, or press enter for none: z\nEnter d, r or c.\n"));
+        assert!(transcript.contains("Choose a printer (press enter to cancel): w\nEnter t, l or s.\n"));
+        assert!(transcript.contains("  Account 52-2222222-1  Bill #6671\n"));
+        assert!(transcript.contains("Stamping 3 bill(s) with \"DUPLICATE\".\n"));
+        assert!(transcript.contains("\rSent bill 3 of 3 ("));
+        assert!(transcript.contains("All bills sent.\n"));
+
+        let ducked = std::fs::read(dir.join("april.dat.DUCKED")).unwrap();
+        assert_eq!(std::fs::read(spool.join("april.dat.DUCKED")).unwrap(), ducked);
+        assert!(!session.has_interrupted_job());
+        // the finished job's journal is cleared away
+        assert_eq!(std::fs::read_dir(dir.join("checkpoints")).unwrap().count(), 0);
+        assert_eq!(audit::verify(&std::fs::read_to_string(dir.join("audit.log")).unwrap()), Ok(2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn near_misses_and_searches() {
+        let (dir, mut session) = scratch_session("session-find");
+        // a mistyped account offers suggestions; then find a bill by name and keep the bills up to it
+        let script = format!("1\n{}\n2\n2\n52-4444444-2\n1\n\n2\n5\nnumber 22\n1\ne\n\nq\ny\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+
+        assert!(transcript.contains("Account ID not found. Did you mean:\n  1) 52-4444444-1"));
+        assert!(transcript.contains("Your processed file is ready: <dir>/april.dat.DUCKED\n"));
+        assert!(transcript.contains("Your processed file is ready: <dir>/april.dat-1.DUCKED\n"));
+        let reread = |name: &str| get_file(&mut Console::new(io::empty(), io::sink()), &dir.join(name)).unwrap().get_bill_count();
+        assert_eq!((reread("april.dat.DUCKED"), reread("april.dat-1.DUCKED")), (4, 2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn menus_follow_the_session() {
+        let (dir, mut session) = scratch_session("session-menus");
+        // only loading can be chosen until a file is loaded, and typos are asked again;
+        // quitting with a file loaded has to be confirmed, and an empty path unloads it
+        let script = format!("2\n1\n{}\nq\nn\n1\n\nq\n", dir.join("april.dat").display());
+        let transcript = play(&dir, &mut session, &script);
+        assert!(transcript.starts_with("Welcome to the Just Ducky Second Chance Bill Handler\n"));
+        assert!(transcript.contains("Select an option: 2\n\nThat option isn't available right now: load a bill file first.\nSelect an option: 1\n"));
+        assert!(transcript.contains("A bill file is loaded. Quit anyway? (y/n) [n]: n\n"));
+        assert!(transcript.ends_with("No luck getting file, returning to main menu.\n\nMain Menu\n=========\n1 Select bill file\n2 Extract bills (load a bill file first)\n\
+                                          3 Replace bulletin message on all bills (load a bill file first)\n\
+                                          4 Printing (nothing to print yet)\n5 Browse bills (load a bill file first)\nq Quit\nSelect an option: q\n\nBye!\n"));
+        assert!(!session.file_ready());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
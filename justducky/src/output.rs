@@ -0,0 +1,275 @@
+//! Naming and writing processed bill files safely.
+//! Output names come from a template, existing files are never replaced unless asked,
+//! and each file is written under a temporary name, synced, checked, and only then
+//! given its real name, so a crash or full disk can't leave a truncated file behind.
+//! Names ending in .gz or .zip are compressed to match.
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckcompress::DuckCompression;
+use duckbill::duckfile::duckerror::DuckError;
+
+use crate::audit::AuditOperation;
+use crate::checkpoint;
+use crate::clock;
+
+/// The name used before templates existed: the input file name plus .DUCKED
+pub const DEFAULT_TEMPLATE: &str = "{input}.DUCKED";
+/// Placeholders a template may use
+const PLACEHOLDERS: [&str; 8] = ["{input}", "{stem}", "{ext}", "{op}", "{first}", "{last}", "{count}", "{date}"];
+/// Give up looking for a free name after this many tries
+const MAX_SUFFIX: usize = 9999;
+
+/// A processed file that has been written
+#[derive(Debug, PartialEq, Clone)]
+pub struct WrittenFile {
+    /// Where it was written, which may not be the name asked for
+    pub path: PathBuf,
+    /// SHA-256 hash of the bytes on disk, after any compression
+    pub sha256: String,
+}
+
+/// How processed files are named and written
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutputPolicy {
+    template: String,
+    overwrite: bool,
+}
+
+impl Default for OutputPolicy {
+    fn default() -> Self {
+        OutputPolicy { template: DEFAULT_TEMPLATE.to_string(), overwrite: false }
+    }
+}
+
+impl OutputPolicy {
+    /// Use a name template such as "{stem}.{op}.{first}-{last}.pcl". Placeholders are:
+    /// {input} input file name, {stem} input name without its extension, {ext} input extension,
+    /// {op} operation, {first} and {last} first and last account IDs, {count} number of bills,
+    /// and {date} today's date as YYYY-MM-DD.
+    /// Files are written beside the input, so the template can't contain a path.
+    pub fn new(template: &str, overwrite: bool) -> Result<OutputPolicy, DuckError> {
+        let mut rest = template.to_string();
+        for p in PLACEHOLDERS {
+            rest = rest.replace(p, "");
+        }
+        if template.trim().is_empty() || rest.contains(['{', '}', '/', '\\']) {
+            return Err(DuckError::BadData);
+        }
+        Ok(OutputPolicy { template: template.to_string(), overwrite })
+    }
+
+    /// Build the policy from $JUSTDUCKY_OUTPUT_TEMPLATE and $JUSTDUCKY_OUTPUT_OVERWRITE (set to 1 to allow replacing files)
+    pub fn from_env() -> Result<OutputPolicy, DuckError> {
+        let template = std::env::var("JUSTDUCKY_OUTPUT_TEMPLATE").unwrap_or(DEFAULT_TEMPLATE.to_string());
+        let overwrite = std::env::var("JUSTDUCKY_OUTPUT_OVERWRITE").is_ok_and(|v| v == "1");
+        OutputPolicy::new(&template, overwrite)
+    }
+
+    /// Fill in the template for these bills, taken from `input` by `operation`
+    pub fn name_for(&self, input: &Path, operation: AuditOperation, bills: &DuckFile) -> PathBuf {
+        let (year, month, day) = clock::today();
+        let input_name = input.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let stem = input.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let ext = input.extension().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let count = bills.get_bill_count();
+        let account = |i: usize| if i < count { bills[i].get_account_id().to_string() } else { String::new() };
+
+        let name = self.template
+            .replace("{input}", &input_name)
+            .replace("{stem}", &stem)
+            .replace("{ext}", &ext)
+            .replace("{op}", &operation.to_string())
+            .replace("{first}", &account(0))
+            .replace("{last}", &account(count.saturating_sub(1)))
+            .replace("{count}", &count.to_string())
+            .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day));
+        input.with_file_name(name.replace(['/', '\\'], "_"))
+    }
+
+    /// Write bills to `path`, or, if that exists and overwriting isn't allowed, to the first free
+    /// name made by adding -1, -2, ... before the extension. Returns where the file was written
+    /// and the hash of what was written there.
+    pub fn write(&self, path: &Path, bills: &DuckFile) -> Result<WrittenFile, DuckError> {
+        let expected = bills.get_bill_count();
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let compression = DuckCompression::from_file_name(&name);
+        let entry_name = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(&name);
+        let mut data = vec![];
+        bills.write_compressed_to(&mut data, compression, entry_name)?;
+        let temp = temp_name(path);
+
+        let result = write_checked(&temp, &data, expected).and_then(|_| self.place(&temp, path));
+        let _ = fs::remove_file(&temp);
+        let placed = result?;
+        sync_dir(&placed);
+        Ok(WrittenFile { path: placed, sha256: checkpoint::data_hash(&data) })
+    }
+
+    /// Give the finished temporary file its final name
+    fn place(&self, temp: &Path, path: &Path) -> Result<PathBuf, DuckError> {
+        if self.overwrite {
+            fs::rename(temp, path)?;
+            return Ok(path.to_path_buf());
+        }
+        place_new(temp, path)
+    }
+}
+
+/// Give a finished temporary file `path`, or if that's taken the first free name made by
+/// adding -1, -2, ... before the extension, never replacing a file. Returns the name used.
+pub fn place_new(temp: &Path, path: &Path) -> Result<PathBuf, DuckError> {
+    for n in 0..=MAX_SUFFIX {
+        let candidate = if n == 0 { path.to_path_buf() } else { with_suffix(path, n) };
+        // a hard link fails if the name is taken, so two writers can't both claim it
+        match fs::hard_link(temp, &candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(_) if !candidate.exists() => {
+                // no hard links on this file system
+                fs::rename(temp, &candidate)?;
+                return Ok(candidate);
+            }
+            Err(_) => continue,
+        }
+    }
+    Err(DuckError::IoError)
+}
+
+/// Get the temporary name a file is written under before it's complete
+fn temp_name(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Add -n before a file name's extension: bills.DUCKED becomes bills-2.DUCKED
+fn with_suffix(path: &Path, n: usize) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}-{}.{}", stem, n, ext),
+        _ => format!("{}-{}", name, n),
+    };
+    path.with_file_name(name)
+}
+
+/// Write data, make sure it reached the disk, then read it back and check it's a bill file
+/// with the expected number of bills
+fn write_checked(path: &Path, data: &[u8], expected: usize) -> Result<(), DuckError> {
+    let mut f = File::create(path)?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    drop(f);
+
+    let written = fs::read(path)?;
+    if written != data {
+        return Err(DuckError::IoError);
+    }
+    let reparsed = DuckFile::read_from(&written[..])?;
+    if reparsed.get_bill_count() != expected {
+        return Err(DuckError::MarkCountMismatch);
+    }
+    Ok(())
+}
+
+/// Make a new name in a folder durable; not every platform can do this, so failures are ignored
+fn sync_dir(path: &Path) {
+    if let Some(dir) = path.parent() {
+        if let Ok(d) = File::open(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) {
+            let _ = d.sync_all();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use duckbill::duckfile::duckbillbuilder::synthetic_file;
+    use duckbill::duckfile::duckdata::DuckData;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("justducky-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn templates_fill_in() {
+        let bills = synthetic_file(3, 38).unwrap();
+        let input = Path::new("/bills/april.dat");
+        let first = bills[0].get_account_id().to_string();
+        let last = bills[2].get_account_id().to_string();
+
+        assert_eq!(OutputPolicy::default().name_for(input, AuditOperation::Range, &bills),
+                   PathBuf::from("/bills/april.dat.DUCKED"));
+        let policy = OutputPolicy::new("{stem}.{op}.{first}-{last}.{count}.pcl", false).unwrap();
+        assert_eq!(policy.name_for(input, AuditOperation::Range, &bills),
+                   PathBuf::from(format!("/bills/april.range.{}-{}.3.pcl", first, last)));
+
+        assert_eq!(OutputPolicy::new("{stem}.{nope}.pcl", false), Err(DuckError::BadData));
+        assert_eq!(OutputPolicy::new("../{stem}.pcl", false), Err(DuckError::BadData));
+        assert_eq!(OutputPolicy::new(" ", false), Err(DuckError::BadData));
+    }
+
+    #[test]
+    fn suffixes_go_before_extension() {
+        assert_eq!(with_suffix(Path::new("/b/april.dat.DUCKED"), 2), PathBuf::from("/b/april.dat-2.DUCKED"));
+        assert_eq!(with_suffix(Path::new("/b/april"), 1), PathBuf::from("/b/april-1"));
+        assert_eq!(with_suffix(Path::new("/b/.hidden"), 1), PathBuf::from("/b/.hidden-1"));
+    }
+
+    #[test]
+    fn existing_files_are_kept() {
+        let dir = scratch_dir("output");
+        let path = dir.join("april.dat.DUCKED");
+        let policy = OutputPolicy::default();
+
+        let first = policy.write(&path, &synthetic_file(2, 38).unwrap()).unwrap().path;
+        let second = policy.write(&path, &synthetic_file(3, 38).unwrap()).unwrap().path;
+        assert_eq!(first, path);
+        assert_eq!(second, dir.join("april.dat-1.DUCKED"));
+        assert_eq!(DuckFile::try_from(DuckData::from(fs::read(&first).unwrap())).unwrap().get_bill_count(), 2);
+        assert_eq!(DuckFile::try_from(DuckData::from(fs::read(&second).unwrap())).unwrap().get_bill_count(), 3);
+
+        let overwriting = OutputPolicy::new(DEFAULT_TEMPLATE, true).unwrap();
+        assert_eq!(overwriting.write(&path, &synthetic_file(4, 38).unwrap()).unwrap().path, path);
+        assert_eq!(DuckFile::try_from(DuckData::from(fs::read(&path).unwrap())).unwrap().get_bill_count(), 4);
+
+        // no temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_by_extension() {
+        let dir = scratch_dir("output-gz");
+        let policy = OutputPolicy::new("{input}.DUCKED.gz", false).unwrap();
+        let path = policy.name_for(&dir.join("april.dat"), AuditOperation::Range, &synthetic_file(2, 38).unwrap());
+        let written = policy.write(&path, &synthetic_file(2, 38).unwrap()).unwrap();
+        assert_eq!(written.path, dir.join("april.dat.DUCKED.gz"));
+
+        let raw = fs::read(&written.path).unwrap();
+        assert_eq!(DuckCompression::detect(&raw), DuckCompression::Gzip);
+        assert_eq!(DuckFile::read_from(&raw[..]).unwrap().get_bill_count(), 2);
+        // the hash is of the compressed bytes on disk, not the bill file inside
+        assert_eq!(written.sha256, checkpoint::data_hash(&raw));
+        assert_ne!(written.sha256, checkpoint::file_hash(&synthetic_file(2, 38).unwrap()));
+
+        let zipped = policy.write(&dir.join("april.zip"), &synthetic_file(1, 38).unwrap()).unwrap();
+        assert_eq!(DuckCompression::detect(&fs::read(&zipped.path).unwrap()), DuckCompression::Zip);
+        assert_eq!(zipped.sha256, checkpoint::data_hash(&fs::read(&zipped.path).unwrap()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_leave_nothing() {
+        let dir = scratch_dir("output-fail");
+        let path = dir.join("missing").join("april.dat.DUCKED");
+        assert!(OutputPolicy::default().write(&path, &synthetic_file(1, 38).unwrap()).is_err());
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
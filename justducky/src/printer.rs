@@ -0,0 +1,312 @@
+//! Ways of getting a processed bill file to a printer: straight to its raw port (JetDirect, 9100),
+//! through the CUPS `lp` command, or into a drop folder watched by a print spooler.
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::net::{Ipv6Addr, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use duckbill::duckfile::DuckFile;
+
+use crate::output;
+
+/// The usual raw printing port
+pub const RAW_PORT: u16 = 9100;
+/// How long to wait for a printer to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for a printer that has stopped taking data, say because it's out of paper
+const WRITE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Why a print job didn't finish
+#[derive(Debug, PartialEq)]
+pub enum PrintError {
+    /// the printer, command or spool folder couldn't be found, or refused the job
+    Failed,
+    /// the job couldn't be sent or written
+    IoError,
+}
+
+/// a short description fit to show a person
+impl Display for PrintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            PrintError::Failed => "printing failed",
+            PrintError::IoError => "the job couldn't be sent to the printer",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+impl From<io::Error> for PrintError {
+    fn from(_: io::Error) -> Self {
+        PrintError::IoError
+    }
+}
+
+/// How far along a print job is, reported after each bill is sent
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PrintProgress {
+    pub bills_sent: usize,
+    pub bill_count: usize,
+    pub bytes_sent: usize,
+    pub byte_count: usize,
+}
+
+/// Somewhere bill files can be printed
+pub trait PrintBackend {
+    /// Describe where the job is going, for the operator
+    fn describe(&self) -> String;
+
+    /// Send the whole file, calling `progress` after each bill
+    fn print(&mut self, bills: &DuckFile, progress: &mut dyn FnMut(PrintProgress)) -> Result<(), PrintError>;
+}
+
+/// Write a bill file to `out` a bill at a time, exactly as it would be saved
+fn send_bills(out: &mut dyn Write, bills: &DuckFile, progress: &mut dyn FnMut(PrintProgress)) -> Result<(), PrintError> {
+    let byte_count = bills.get_header().len()
+        + bills[..].iter().map(|b| b.get_raw().len()).sum::<usize>()
+        + bills.get_footer().len();
+    let mut status = PrintProgress { bills_sent: 0, bill_count: bills.get_bill_count(), bytes_sent: 0, byte_count };
+
+    out.write_all(bills.get_header().as_ref())?;
+    status.bytes_sent += bills.get_header().len();
+    for bill in bills[..].iter() {
+        out.write_all(bill.get_raw().as_ref())?;
+        status.bills_sent += 1;
+        status.bytes_sent += bill.get_raw().len();
+        progress(status);
+    }
+    out.write_all(bills.get_footer().as_ref())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// A printer reached directly over the network, usually on port 9100
+pub struct RawTcpPrinter {
+    address: String,
+}
+
+impl RawTcpPrinter {
+    /// Use a printer at "host" or "host:port"; the port defaults to 9100.
+    /// IPv6 addresses take a port as "[::1]:9100".
+    pub fn new(address: &str) -> RawTcpPrinter {
+        let address = address.trim();
+        if let Ok(ip) = address.parse::<Ipv6Addr>() {
+            return RawTcpPrinter { address: format!("[{}]:{}", ip, RAW_PORT) };
+        }
+        let has_port = address.rsplit_once(':').is_some_and(|(host, port)| !host.ends_with(':') && port.parse::<u16>().is_ok());
+        RawTcpPrinter {
+            address: if has_port { address.to_string() } else { format!("{}:{}", address, RAW_PORT) },
+        }
+    }
+}
+
+impl PrintBackend for RawTcpPrinter {
+    fn describe(&self) -> String {
+        format!("printer at {}", self.address)
+    }
+
+    fn print(&mut self, bills: &DuckFile, progress: &mut dyn FnMut(PrintProgress)) -> Result<(), PrintError> {
+        let addr = self.address.to_socket_addrs()?.next().ok_or(PrintError::Failed)?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        send_bills(&mut stream, bills, progress)?;
+        stream.shutdown(std::net::Shutdown::Write)?;
+        Ok(())
+    }
+}
+
+/// A CUPS queue, printed to with `lp -o raw` so the bills aren't filtered
+pub struct LpPrinter {
+    program: String,
+    args: Vec<String>,
+}
+
+impl LpPrinter {
+    /// Print to the named queue, or the default queue if None
+    pub fn new(destination: Option<&str>) -> LpPrinter {
+        let mut args = vec!["-o", "raw"];
+        if let Some(d) = destination {
+            args.extend(["-d", d]);
+        }
+        LpPrinter::with_command("lp", &args)
+    }
+
+    /// Print through some other command that reads the job from its standard input
+    pub fn with_command(program: &str, args: &[&str]) -> LpPrinter {
+        LpPrinter { program: program.to_string(), args: args.iter().map(|a| a.to_string()).collect() }
+    }
+}
+
+impl PrintBackend for LpPrinter {
+    fn describe(&self) -> String {
+        format!("{} {}", self.program, self.args.join(" "))
+    }
+
+    fn print(&mut self, bills: &DuckFile, progress: &mut dyn FnMut(PrintProgress)) -> Result<(), PrintError> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        let sent = match child.stdin.take() {
+            Some(mut stdin) => send_bills(&mut stdin, bills, progress),
+            None => Err(PrintError::Failed),
+        };
+        let status = child.wait()?;
+        sent?;
+        if status.success() { Ok(()) } else { Err(PrintError::Failed) }
+    }
+}
+
+/// A drop folder watched by a print spooler. Each job is written under a temporary
+/// name and renamed when complete, so the spooler never picks up half a file. A job
+/// already waiting under the same name is never replaced; the new one gets a -1, -2, ...
+/// suffix instead.
+pub struct SpoolDirPrinter {
+    dir: PathBuf,
+    job_name: String,
+    placed: Option<PathBuf>,
+}
+
+impl SpoolDirPrinter {
+    pub fn new(dir: PathBuf, job_name: &str) -> SpoolDirPrinter {
+        SpoolDirPrinter { dir, job_name: job_name.to_string(), placed: None }
+    }
+}
+
+impl PrintBackend for SpoolDirPrinter {
+    fn describe(&self) -> String {
+        match &self.placed {
+            // once printed, name the job itself, which is what the audit log wants
+            Some(job) => format!("spool file {}", job.display()),
+            None => format!("spool folder {}", self.dir.display()),
+        }
+    }
+
+    fn print(&mut self, bills: &DuckFile, progress: &mut dyn FnMut(PrintProgress)) -> Result<(), PrintError> {
+        if !self.dir.is_dir() {
+            return Err(PrintError::Failed);
+        }
+        let partial = self.dir.join(format!(".{}.{}.part", self.job_name, std::process::id()));
+        let result = File::create(&partial)
+            .map_err(PrintError::from)
+            .and_then(|mut f| {
+                send_bills(&mut f, bills, progress)?;
+                f.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| output::place_new(&partial, &self.dir.join(&self.job_name)).map_err(|_| PrintError::IoError));
+        let _ = fs::remove_file(&partial);
+        self.placed = Some(result?);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use duckbill::duckfile::duckbillbuilder::synthetic_file;
+    use duckbill::duckfile::duckdata::DuckData;
+
+    fn expected_bytes(bills: &DuckFile) -> Vec<u8> {
+        let data: DuckData = DuckFile::try_from(bills[..].to_vec()).unwrap().into();
+        data.as_ref().to_vec()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("justducky-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn addresses_get_default_port() {
+        assert_eq!(RawTcpPrinter::new("printer1").address, "printer1:9100");
+        assert_eq!(RawTcpPrinter::new("10.0.0.5:9101").address, "10.0.0.5:9101");
+        assert_eq!(RawTcpPrinter::new("::1").address, "[::1]:9100");
+        assert_eq!(RawTcpPrinter::new("fe80::1:9101").address, "[fe80::1:9101]:9100");
+        assert_eq!(RawTcpPrinter::new("[::1]:9101").address, "[::1]:9101");
+    }
+
+    #[test]
+    fn raw_tcp_delivers_exact_bytes() {
+        let bills = synthetic_file(5, 35).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let printer = std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut received = vec![];
+            conn.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let mut updates = vec![];
+        RawTcpPrinter::new(&format!("127.0.0.1:{}", port))
+            .print(&bills, &mut |p| updates.push(p))
+            .unwrap();
+
+        assert_eq!(printer.join().unwrap(), expected_bytes(&bills));
+        assert_eq!(updates.len(), 5);
+        assert_eq!(updates.iter().map(|p| p.bills_sent).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(updates.windows(2).all(|w| w[0].bytes_sent < w[1].bytes_sent));
+        assert_eq!(updates[4].byte_count, expected_bytes(&bills).len());
+        assert_eq!(updates[4].byte_count - updates[4].bytes_sent, bills.get_footer().len());
+    }
+
+    #[test]
+    fn refused_connection_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let bills = synthetic_file(1, 35).unwrap();
+        assert!(RawTcpPrinter::new(&format!("127.0.0.1:{}", port)).print(&bills, &mut |_| {}).is_err());
+    }
+
+    #[test]
+    fn spool_dir_gets_whole_file() {
+        let dir = scratch_dir("spool");
+        let bills = synthetic_file(3, 35).unwrap();
+        let mut printer = SpoolDirPrinter::new(dir.clone(), "job1.pcl");
+        printer.print(&bills, &mut |_| {}).unwrap();
+
+        assert_eq!(printer.describe(), format!("spool file {}", dir.join("job1.pcl").display()));
+        assert_eq!(fs::read(dir.join("job1.pcl")).unwrap(), expected_bytes(&bills));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // a job still waiting under that name is kept
+        let fewer = synthetic_file(1, 35).unwrap();
+        printer.print(&fewer, &mut |_| {}).unwrap();
+        assert_eq!(printer.describe(), format!("spool file {}", dir.join("job1-1.pcl").display()));
+        assert_eq!(fs::read(dir.join("job1.pcl")).unwrap(), expected_bytes(&bills));
+        assert_eq!(fs::read(dir.join("job1-1.pcl")).unwrap(), expected_bytes(&fewer));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(printer.print(&bills, &mut |_| {}), Err(PrintError::Failed));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_gets_job_on_stdin() {
+        let dir = scratch_dir("lp");
+        let out = dir.join("job.pcl");
+        let bills = synthetic_file(2, 35).unwrap();
+        let script = format!("cat > '{}'", out.display());
+        LpPrinter::with_command("sh", &["-c", &script]).print(&bills, &mut |_| {}).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), expected_bytes(&bills));
+
+        assert_eq!(LpPrinter::with_command("sh", &["-c", "cat > /dev/null; exit 3"]).print(&bills, &mut |_| {}),
+                   Err(PrintError::Failed));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,782 @@
+//! A small web interface for desktops that can't run console tools:
+//!
+//!     justducky serve [--port PORT] [--bind ADDR]
+//!
+//! It listens on 127.0.0.1 unless --bind says otherwise, and serves one person at a time.
+//! A bill file is uploaded from the browser (or with curl --data-binary to /upload?name=FILE),
+//! then its bills can be searched, browsed and picked, and the picked bills downloaded as a
+//! .DUCKED file. Everything is done in memory; extracts are recorded in the audit log.
+//!
+//! Only requests addressed to 127.0.0.1, localhost or the --bind address are answered, so
+//! another site's name pointed at this computer (DNS rebinding) can't read the bills, and
+//! forms are only accepted from this server's own pages, so another site can't post to it.
+//! Other computers use this computer's own address, so a wildcard --bind such as 0.0.0.0
+//! only serves this computer; to share, --bind the address the others will type.
+use std::fmt::Write as _;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use duckbill::duckfile::DuckFile;
+use duckbill::duckfile::duckacctid::DuckAcctId;
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckmeter::format_amount;
+
+use crate::audit::{self, AuditEntry, AuditOperation, AuditSource};
+use crate::checkpoint;
+
+pub const DEFAULT_PORT: u16 = 8787;
+
+pub const USAGE: &str = "usage: justducky serve [--port PORT] [--bind ADDR]
+  --port PORT  port to listen on (default 8787)
+  --bind ADDR  address to listen on (default 127.0.0.1, this computer only);
+               use this computer's own address to serve others, not 0.0.0.0";
+
+/// Largest request accepted, which has to fit a whole bill file
+const MAX_REQUEST: usize = 150 * 1024 * 1024;
+/// Longest request line and headers accepted, together
+const MAX_HEAD: u64 = 8 * 1024;
+/// Most header lines accepted
+const MAX_HEADERS: usize = 64;
+/// Bills listed on each page
+const PAGE_SIZE: usize = 50;
+/// How long a connection may sit idle before it's dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a whole request may take to arrive, however slowly it trickles in.
+/// Only one connection is served at a time, so this is the longest anyone can hold it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Where to listen
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServeArgs {
+    pub bind: IpAddr,
+    pub port: u16,
+}
+
+pub fn parse_args(args: &[String]) -> Result<ServeArgs, String> {
+    let mut parsed = ServeArgs { bind: IpAddr::V4(Ipv4Addr::LOCALHOST), port: DEFAULT_PORT };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--port" => parsed.port = value("--port")?.parse().map_err(|_| "--port needs a port number".to_string())?,
+            "--bind" => parsed.bind = value("--bind")?.parse().map_err(|_| "--bind needs an IP address".to_string())?,
+            other => return Err(format!("unexpected argument {}", other)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Run the server until it's stopped; returns the process exit code if it can't start
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("justducky: {}\n{}", message, USAGE);
+            return 2;
+        }
+    };
+    let listener = match TcpListener::bind((args.bind, args.port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("justducky: can't listen on {}:{} ({})", args.bind, args.port, e);
+            return 1;
+        }
+    };
+    if args.bind.is_unspecified() {
+        eprintln!("justducky: WARNING: listening on every address, but only requests to 127.0.0.1 or localhost are answered; \
+                   give --bind this computer's own address to serve other computers");
+    } else if !args.bind.is_loopback() {
+        eprintln!("justducky: WARNING: listening on {}, so other computers can reach these bills", args.bind);
+    }
+    eprintln!("justducky: open http://{}:{}/ in a browser; press Ctrl+C to stop", args.bind, args.port);
+
+    let mut state = ServeState::new(audit::default_path(), allowed_hosts(&args));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = serve_connection(stream, &mut state) {
+                    eprintln!("justducky: connection failed ({})", e);
+                }
+            }
+            Err(e) => eprintln!("justducky: connection failed ({})", e),
+        }
+    }
+    0
+}
+
+/// Answer the one request on a connection
+fn serve_connection(stream: TcpStream, state: &mut ServeState) -> io::Result<()> {
+    let mut reader = BufReader::new(Deadline { stream: stream.try_clone()?, end: Instant::now() + REQUEST_TIMEOUT });
+    let response = match read_request(&mut reader) {
+        Ok(request) => handle(state, &request),
+        Err(response) => response,
+    };
+    // a client that never reads its download mustn't hold the server either
+    stream.set_write_timeout(Some(IDLE_TIMEOUT))?;
+    response.write_to(stream)
+}
+
+/// A connection that stops reading at a set time, and after IDLE_TIMEOUT without data
+struct Deadline {
+    stream: TcpStream,
+    end: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the request took too long"));
+        }
+        self.stream.set_read_timeout(Some(left.min(IDLE_TIMEOUT)))?;
+        self.stream.read(buf)
+    }
+}
+
+/// A request as far as this server cares
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    host: String,
+    origin: Option<String>,
+    referer: Option<String>,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// The first value of a query parameter
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Response {
+    status: u16,
+    content_type: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn html(body: String) -> Response {
+        Response { status: 200, content_type: "text/html; charset=utf-8".to_string(), headers: vec![], body: body.into_bytes() }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response { status, content_type: "text/plain; charset=utf-8".to_string(), headers: vec![], body: message.as_bytes().to_vec() }
+    }
+
+    /// Send the browser back to a page, after a form has done its work
+    fn see_other(location: &str) -> Response {
+        Response {
+            status: 303,
+            content_type: "text/plain; charset=utf-8".to_string(),
+            headers: vec![("Location".to_string(), location.to_string())],
+            body: vec![],
+        }
+    }
+
+    fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            303 => "See Other",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            _ => "Error",
+        };
+        write!(out, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+               self.status, reason, self.content_type, self.body.len())?;
+        for (name, value) in &self.headers {
+            write!(out, "{}: {}\r\n", name, value)?;
+        }
+        write!(out, "\r\n")?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+/// Read a request: the request line, headers, and a body of Content-Length bytes.
+/// The request line and headers must fit in MAX_HEAD bytes; the body is read as it
+/// arrives rather than set aside in full up front.
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Response> {
+    let bad = |_| Response::error(400, "That request couldn't be read.");
+    let too_long = || Response::error(431, "That request's headers are too long.");
+    let mut head = reader.by_ref().take(MAX_HEAD);
+    let mut line = String::new();
+    head.read_line(&mut line).map_err(bad)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(Response::error(414, "That address is too long."));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Response::error(400, "That request couldn't be read."));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: form_decode(query),
+        ..Request::default()
+    };
+
+    let mut length = 0;
+    for count in 0.. {
+        let mut header = String::new();
+        head.read_line(&mut header).map_err(bad)?;
+        if !header.ends_with('\n') {
+            // the headers ran past MAX_HEAD, or the connection closed partway through them
+            return Err(if head.limit() == 0 { too_long() } else { Response::error(400, "That request couldn't be read.") });
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(too_long());
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().map_err(|_| Response::error(400, "Bad Content-Length."))?,
+            "content-type" => request.content_type = value.trim().to_string(),
+            "host" => request.host = value.trim().to_ascii_lowercase(),
+            "origin" => request.origin = Some(value.trim().to_string()),
+            "referer" => request.referer = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if length > MAX_REQUEST {
+        return Err(Response::error(413, "That file is too big."));
+    }
+    reader.take(length as u64).read_to_end(&mut request.body).map_err(bad)?;
+    if request.body.len() < length {
+        return Err(Response::error(400, "That request couldn't be read."));
+    }
+    Ok(request)
+}
+
+/// Split a query string or urlencoded form into its names and values
+fn form_decode(form: &str) -> Vec<(String, String)> {
+    form.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (url_decode(name), url_decode(value))
+        })
+        .collect()
+}
+
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn url_encode(text: &str) -> String {
+    text.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+/// Find the uploaded file in a multipart/form-data body: its file name and contents
+fn multipart_file(content_type: &str, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    let boundary = content_type.split(';').find_map(|p| p.trim().strip_prefix("boundary="))?.trim_matches('"');
+    let delimiter = format!("\r\n--{}", boundary);
+    // the first delimiter has no line break before it
+    let mut rest = body.strip_prefix(&delimiter.as_bytes()[2..])?;
+    while let Some(end) = find(rest, delimiter.as_bytes()) {
+        let part = &rest[..end];
+        rest = &rest[end + delimiter.len()..];
+        let Some(header_end) = find(part, b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&part[..header_end]);
+        let filename = headers.split(';')
+            .find_map(|h| h.trim().strip_prefix("filename="))
+            .map(|f| f.lines().next().unwrap_or_default().trim().trim_matches('"').to_string());
+        if let Some(filename) = filename {
+            return Some((filename, part[header_end + 4..].to_vec()));
+        }
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// The bill file being worked on
+struct Loaded {
+    name: String,
+    bills: DuckFile,
+    source: AuditSource,
+}
+
+struct ServeState {
+    loaded: Option<Loaded>,
+    /// shown once on the next page, after a form sends the browser back
+    message: Option<String>,
+    audit_log: PathBuf,
+    /// Host headers this server answers to, as host:port
+    hosts: Vec<String>,
+}
+
+impl ServeState {
+    fn new(audit_log: PathBuf, hosts: Vec<String>) -> ServeState {
+        ServeState { loaded: None, message: None, audit_log, hosts }
+    }
+
+    /// Whether a post came from one of this server's own pages. Browsers send Origin, or at
+    /// least Referer, with every form post; tools like curl send neither and are let through.
+    fn same_origin(&self, request: &Request) -> bool {
+        let ours = |host: &String| format!("http://{}", host);
+        match (&request.origin, &request.referer) {
+            (Some(origin), _) => self.hosts.iter().any(|h| *origin == ours(h)),
+            (None, Some(referer)) => self.hosts.iter().any(|h| referer.starts_with(&format!("{}/", ours(h)))),
+            (None, None) => true,
+        }
+    }
+}
+
+/// The Host headers a browser sends when it means this server: 127.0.0.1, localhost or
+/// the address listened on, with the port, or without it on port 80 where browsers leave
+/// it out. A wildcard address such as 0.0.0.0 can't be matched to the names other
+/// computers know this one by, so only 127.0.0.1 and localhost work then.
+fn allowed_hosts(args: &ServeArgs) -> Vec<String> {
+    let bound = match args.bind {
+        IpAddr::V6(ip) => format!("[{}]", ip),
+        ip => ip.to_string(),
+    };
+    let mut hosts = vec![];
+    for name in ["127.0.0.1", "localhost", &bound] {
+        let with_port = format!("{}:{}", name, args.port);
+        for host in [Some(with_port), (args.port == 80).then(|| name.to_string())].into_iter().flatten() {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    hosts
+}
+
+fn handle(state: &mut ServeState, request: &Request) -> Response {
+    if !state.hosts.contains(&request.host) {
+        return Response::error(403, "This server only answers to its own address.");
+    }
+    if request.method == "POST" && !state.same_origin(request) {
+        return Response::error(403, "Forms can only be sent from this server's own pages.");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => index_page(state, request),
+        ("GET", "/bill") => bill_page(state, request),
+        ("POST", "/upload") => upload(state, request),
+        ("POST", "/extract") => extract(state, request),
+        (_, "/" | "/bill" | "/upload" | "/extract") => Response::error(405, "That can't be done here."),
+        _ => Response::error(404, "There's nothing here."),
+    }
+}
+
+/// Start a page, with any message waiting to be shown
+fn page_start(title: &str, message: Option<String>) -> String {
+    let mut html = format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\
+                            <style>body{{font-family:sans-serif}} td,th{{padding:2px 8px;text-align:left}} \
+                            .msg{{background:#ffd}}</style></head><body>\n<h1>{}</h1>\n", html_escape(title), html_escape(title));
+    if let Some(message) = message {
+        let _ = writeln!(html, "<p class=\"msg\">{}</p>", html_escape(&message));
+    }
+    html
+}
+
+fn index_page(state: &mut ServeState, request: &Request) -> Response {
+    let mut html = page_start("Just Ducky", state.message.take());
+    html.push_str("<form method=\"post\" action=\"/upload\" enctype=\"multipart/form-data\">\
+                   <input type=\"file\" name=\"bills\"> <button>Load bill file</button></form>\n");
+    let Some(loaded) = &state.loaded else {
+        html.push_str("<p>No bill file loaded.</p></body></html>\n");
+        return Response::html(html);
+    };
+
+    let search = request.param("q").unwrap_or_default().trim();
+    let found = find_bills(&loaded.bills, search);
+    let page: usize = request.param("page").and_then(|p| p.parse().ok()).unwrap_or(0);
+    let pages = found.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    let _ = writeln!(html, "<p>{}: {} bills, {} pages</p>", html_escape(&loaded.name), loaded.bills.get_bill_count(), loaded.bills.page_count());
+    let _ = writeln!(html, "<form method=\"get\" action=\"/\"><input name=\"q\" value=\"{}\" placeholder=\"account, name or address\"> \
+                            <button>Search</button></form>", html_escape(search));
+    if !search.is_empty() {
+        let _ = writeln!(html, "<p>{} bills match &ldquo;{}&rdquo;. <a href=\"/\">Show all</a></p>", found.len(), html_escape(search));
+    }
+
+    html.push_str("<form method=\"post\" action=\"/extract\">\n<table>\n\
+                   <tr><th></th><th>#</th><th>Account</th><th>Bill #</th><th>Name</th><th>Amount due</th></tr>\n");
+    for &i in found.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        let bill = &loaded.bills[i];
+        let _ = writeln!(html, "<tr><td><input type=\"checkbox\" name=\"bill\" value=\"{i}\"></td><td><a href=\"/bill?i={i}\">{}</a></td>\
+                                <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                         i + 1, bill.get_account_id(), bill.get_bill_number(), html_escape(&bill.get_name().unwrap_or_default()),
+                         bill.get_amount_due().map(format_amount).unwrap_or_default());
+    }
+    html.push_str("</table>\n");
+    if pages > 1 {
+        let link = |p: usize, text: &str| format!("<a href=\"/?q={}&amp;page={}\">{}</a>", url_encode(search), p, text);
+        let _ = writeln!(html, "<p>{} Page {} of {} {}</p>",
+                         if page > 0 { link(page - 1, "&larr; Previous") } else { String::new() },
+                         page + 1, pages,
+                         if page + 1 < pages { link(page + 1, "Next &rarr;") } else { String::new() });
+    }
+    html.push_str("<p><button name=\"mode\" value=\"only\">Download the picked bills</button> \
+                   <button name=\"mode\" value=\"range\">Download every bill from the first picked to the last</button></p>\n\
+                   </form></body></html>\n");
+    Response::html(html)
+}
+
+/// The bills to list for a search: every bill if there's no search, otherwise the bill
+/// with that account ID followed by bills whose name or address matches
+fn find_bills(bills: &DuckFile, search: &str) -> Vec<usize> {
+    if search.is_empty() {
+        return (0..bills.get_bill_count()).collect();
+    }
+    let mut found: Vec<usize> = DuckAcctId::normalize(search).ok()
+        .and_then(|acct| bills.get_index_of_account(&acct))
+        .into_iter()
+        .collect();
+    for m in bills.search_text(search) {
+        if !found.contains(&m.get_index()) {
+            found.push(m.get_index());
+        }
+    }
+    found
+}
+
+fn bill_page(state: &mut ServeState, request: &Request) -> Response {
+    let Some(loaded) = &state.loaded else {
+        return Response::see_other("/");
+    };
+    let Some(i) = request.param("i").and_then(|i| i.parse::<usize>().ok()).filter(|&i| i < loaded.bills.get_bill_count()) else {
+        return Response::error(404, "There's no such bill.");
+    };
+    let bill = &loaded.bills[i];
+
+    let mut html = page_start(&format!("Bill {} of {}", i + 1, loaded.bills.get_bill_count()), state.message.take());
+    html.push_str("<p><a href=\"/\">&larr; All bills</a></p>\n<table>\n");
+    let rows = [
+        ("Account ID", bill.get_account_id().to_string()),
+        ("Bill number", bill.get_bill_number().to_string()),
+        ("Name", bill.get_name().unwrap_or_default()),
+        ("Service address", bill.get_service_address().join(", ")),
+        ("Mailing address", bill.get_mailing_address().join(", ")),
+        ("Amount due", bill.get_amount_due().map(format_amount).unwrap_or_default()),
+        ("Size", format!("{} bytes", bill.get_raw().len())),
+    ];
+    for (name, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", name, html_escape(&value));
+    }
+    html.push_str("</table>\n<h2>Meter readings</h2>\n<pre>");
+    for reading in bill.get_meter_readings() {
+        let _ = writeln!(html, "{}", html_escape(&reading.to_line()));
+    }
+    html.push_str("</pre>\n<h2>Printed text</h2>\n<pre>");
+    for line in bill.get_plain_text() {
+        let _ = writeln!(html, "{}", html_escape(&line));
+    }
+    let _ = writeln!(html, "</pre>\n<form method=\"post\" action=\"/extract\"><input type=\"hidden\" name=\"bill\" value=\"{}\">\
+                            <button name=\"mode\" value=\"only\">Download this bill</button></form></body></html>", i);
+    Response::html(html)
+}
+
+fn upload(state: &mut ServeState, request: &Request) -> Response {
+    let (name, data) = if request.content_type.starts_with("multipart/form-data") {
+        match multipart_file(&request.content_type, &request.body) {
+            Some(file) => file,
+            None => return Response::error(400, "No file was uploaded."),
+        }
+    } else {
+        (request.param("name").unwrap_or("upload.dat").to_string(), request.body.clone())
+    };
+    // browsers may send a whole path; only the file's own name is wanted
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().to_string();
+
+    state.message = Some(match DuckFile::read_from(&data[..]) {
+        Ok(bills) => {
+            let message = format!("Loaded {}: {} bills.", name, bills.get_bill_count());
+            let source = AuditSource { path: PathBuf::from(&name), sha256: checkpoint::file_hash(&bills) };
+            state.loaded = Some(Loaded { name, bills, source });
+            message
+        }
+        Err(e) => format!("{} couldn't be loaded: {}.", name, e),
+    });
+    Response::see_other("/")
+}
+
+fn extract(state: &mut ServeState, request: &Request) -> Response {
+    let Some(loaded) = &state.loaded else {
+        return Response::see_other("/");
+    };
+    let form = form_decode(&String::from_utf8_lossy(&request.body));
+    let count = loaded.bills.get_bill_count();
+    let mut picked: Vec<usize> = form.iter()
+        .filter(|(name, _)| name == "bill")
+        .filter_map(|(_, value)| value.parse().ok())
+        .filter(|&i| i < count)
+        .collect();
+    picked.sort_unstable();
+    picked.dedup();
+    let (Some(&first), Some(&last)) = (picked.first(), picked.last()) else {
+        state.message = Some("Pick at least one bill first.".to_string());
+        return Response::see_other("/");
+    };
+
+    let range = form.iter().any(|(name, value)| name == "mode" && value == "range");
+    let (operation, selected): (AuditOperation, Vec<DuckBill>) = match (range, picked.len()) {
+        (true, _) => (AuditOperation::Range, loaded.bills[first..=last].to_vec()),
+        (false, 1) => (AuditOperation::Single, loaded.bills[first..=first].to_vec()),
+        (false, _) => (AuditOperation::Picked, picked.iter().map(|&i| loaded.bills[i].clone()).collect()),
+    };
+
+    let processed = match DuckFile::try_from(selected) {
+        Ok(processed) => processed,
+        Err(e) => return Response::error(500, &format!("The file couldn't be made: {}.", e)),
+    };
+    let mut data = vec![];
+    if let Err(e) = processed.write_to(&mut data) {
+        return Response::error(500, &format!("The file couldn't be made: {}.", e));
+    }
+
+    let filename = format!("{}.DUCKED", loaded.name).replace(['"', '\r', '\n'], "_");
+    let output = format!("download:{}", filename);
+    let output_hash = checkpoint::file_hash(&processed);
+    let entry = AuditEntry { operation, source: &loaded.source, bills: &processed[..], output: &output, output_sha256: &output_hash };
+    if audit::append(&state.audit_log, &entry).is_err() {
+        eprintln!("justducky: WARNING: this extraction could not be recorded in the audit log {}", state.audit_log.display());
+    }
+
+    Response {
+        status: 200,
+        content_type: "application/octet-stream".to_string(),
+        headers: vec![("Content-Disposition".to_string(), format!("attachment; filename=\"{}\"", filename))],
+        body: data,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn sample() -> Vec<u8> {
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../duckbill/test_data/anon_bill_file_4.dat")).unwrap()
+    }
+
+    fn state(audit_log: PathBuf) -> ServeState {
+        ServeState::new(audit_log, allowed_hosts(&parse_args(&[]).unwrap()))
+    }
+
+    fn get(target: &str) -> Request {
+        read_request(&mut format!("GET {} HTTP/1.1\r\nHost: localhost:8787\r\n\r\n", target).as_bytes()).unwrap()
+    }
+
+    fn post(target: &str, content_type: &str, body: &[u8]) -> Request {
+        let mut raw = format!("POST {} HTTP/1.1\r\nHost: 127.0.0.1:8787\r\nOrigin: http://127.0.0.1:8787\r\n\
+                               Content-Type: {}\r\nContent-Length: {}\r\n\r\n", target, content_type, body.len()).into_bytes();
+        raw.extend_from_slice(body);
+        read_request(&mut &raw[..]).unwrap()
+    }
+
+    fn text(response: &Response) -> String {
+        String::from_utf8_lossy(&response.body).to_string()
+    }
+
+    #[test]
+    fn arguments() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        assert_eq!(parse_args(&[]), Ok(ServeArgs { bind: IpAddr::V4(Ipv4Addr::LOCALHOST), port: DEFAULT_PORT }));
+        let parsed = parse_args(&args(&["--port", "9000", "--bind", "0.0.0.0"])).unwrap();
+        assert_eq!((parsed.port, parsed.bind.is_loopback()), (9000, false));
+        assert!(parse_args(&args(&["--port", "big"])).is_err());
+        assert!(parse_args(&args(&["--bind"])).is_err());
+        assert!(parse_args(&args(&["extra"])).is_err());
+    }
+
+    #[test]
+    fn requests_and_forms() {
+        let request = get("/bill?i=2&q=main+st%2C%20apt");
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/bill"));
+        assert_eq!(request.param("q"), Some("main st, apt"));
+        assert_eq!(request.param("i"), Some("2"));
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(url_decode("%zz%e2%82%ac"), "%zz\u{20ac}");
+        assert_eq!(url_encode("a b&c"), "a%20b%26c");
+        assert_eq!(html_escape("<b>\"A&B\"</b>"), "&lt;b&gt;&quot;A&amp;B&quot;&lt;/b&gt;");
+
+        let request = post("/extract", "application/x-www-form-urlencoded", b"bill=1&bill=3&mode=only");
+        assert_eq!(form_decode(&String::from_utf8_lossy(&request.body)).len(), 3);
+
+        let too_big = format!("POST /upload HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_REQUEST + 1);
+        assert_eq!(read_request(&mut too_big.as_bytes()).unwrap_err().status, 413);
+        assert_eq!(read_request(&mut "\r\n".as_bytes()).unwrap_err().status, 400);
+        let short = "POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort";
+        assert_eq!(read_request(&mut short.as_bytes()).unwrap_err().status, 400);
+        let unfinished = "GET / HTTP/1.1\r\nHost: localhost:8787\r\n";
+        assert_eq!(read_request(&mut unfinished.as_bytes()).unwrap_err().status, 400);
+        let long_header = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "x".repeat(MAX_HEAD as usize));
+        assert_eq!(read_request(&mut long_header.as_bytes()).unwrap_err().status, 431);
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(read_request(&mut many_headers.as_bytes()).unwrap_err().status, 431);
+        let endless_line = format!("GET /{}", "x".repeat(MAX_HEAD as usize * 4));
+        assert_eq!(read_request(&mut endless_line.as_bytes()).unwrap_err().status, 414);
+
+        let body = b"--XyZ\r\nContent-Disposition: form-data; name=\"bills\"; filename=\"C:\\\\runs\\\\april.dat\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\nDATA\r\n--XyZ\r\n--XyZ--\r\n";
+        assert_eq!(multipart_file("multipart/form-data; boundary=XyZ", body),
+                   Some(("C:\\\\runs\\\\april.dat".to_string(), b"DATA".to_vec())));
+        assert_eq!(multipart_file("multipart/form-data; boundary=Other", body), None);
+    }
+
+    #[test]
+    fn upload_browse_and_download() {
+        let dir = std::env::temp_dir().join(format!("justducky-serve-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut state = state(dir.join("audit.log"));
+        assert!(text(&handle(&mut state, &get("/"))).contains("No bill file loaded."));
+        assert_eq!(handle(&mut state, &get("/nowhere")).status, 404);
+        assert_eq!(handle(&mut state, &get("/upload")).status, 405);
+
+        let mut body = b"--b\r\nContent-Disposition: form-data; name=\"bills\"; filename=\"april.dat\"\r\n\r\n".to_vec();
+        body.extend(sample());
+        body.extend(b"\r\n--b--\r\n");
+        let response = handle(&mut state, &post("/upload", "multipart/form-data; boundary=b", &body));
+        assert_eq!((response.status, &response.headers[0].1[..]), (303, "/"));
+
+        let index = text(&handle(&mut state, &get("/")));
+        assert!(index.contains("Loaded april.dat: 4 bills."));
+        assert!(index.contains("<td>52-2222222-1</td><td>6671</td><td>NAME NUMBER 22</td><td>$69.15</td>"));
+        // the message is only shown once
+        assert!(!text(&handle(&mut state, &get("/"))).contains("Loaded april.dat"));
+
+        let search = text(&handle(&mut state, &get("/?q=three")));
+        assert!(search.contains("1 bills match") && search.contains("52-3333333-1") && !search.contains("52-1111111-1"));
+        assert!(text(&handle(&mut state, &get("/?q=5244444441"))).contains("52-4444444-1"));
+
+        let detail = text(&handle(&mut state, &get("/bill?i=2")));
+        assert!(detail.contains("<title>Bill 3 of 4</title>") && detail.contains("NAME THREE THREE33"));
+        assert_eq!(handle(&mut state, &get("/bill?i=9")).status, 404);
+
+        let none = handle(&mut state, &post("/extract", "application/x-www-form-urlencoded", b"mode=only"));
+        assert_eq!(none.status, 303);
+        let download = handle(&mut state, &post("/extract", "application/x-www-form-urlencoded", b"bill=3&bill=1&mode=only"));
+        assert_eq!(download.headers[0].1, "attachment; filename=\"april.dat.DUCKED\"");
+        let picked = DuckFile::read_from(&download.body[..]).unwrap();
+        assert_eq!(picked[..].iter().map(|b| b.get_account_id().to_string()).collect::<Vec<_>>(), ["52-2222222-1", "52-4444444-1"]);
+        let range = handle(&mut state, &post("/extract", "application/x-www-form-urlencoded", b"bill=3&bill=1&mode=range"));
+        assert_eq!(DuckFile::read_from(&range.body[..]).unwrap().get_bill_count(), 3);
+        assert_eq!(audit::verify(&std::fs::read_to_string(dir.join("audit.log")).unwrap()), Ok(2));
+
+        // a raw upload that isn't a bill file leaves the loaded one alone
+        handle(&mut state, &post("/upload?name=junk.txt", "application/octet-stream", b"not bills"));
+        let index = text(&handle(&mut state, &get("/")));
+        assert!(index.contains("junk.txt couldn&#39;t be loaded") && index.contains("april.dat: 4 bills"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_sites_are_refused() {
+        let mut state = state(std::env::temp_dir().join("justducky-serve-unused.log"));
+        let request = |raw: &str| read_request(&mut raw.as_bytes()).unwrap();
+        let form = "Content-Type: application/x-www-form-urlencoded\r\nContent-Length: 8\r\n\r\nbill=1&x";
+
+        // a rebound name, or no Host at all
+        assert_eq!(handle(&mut state, &request("GET / HTTP/1.1\r\nHost: attacker.example:8787\r\n\r\n")).status, 403);
+        assert_eq!(handle(&mut state, &request("GET /bill?i=0 HTTP/1.1\r\n\r\n")).status, 403);
+        assert_eq!(handle(&mut state, &request("GET / HTTP/1.1\r\nHost: LOCALHOST:8787\r\n\r\n")).status, 200);
+
+        // a form posted from another site's page
+        let cross = format!("POST /extract HTTP/1.1\r\nHost: localhost:8787\r\nOrigin: http://attacker.example\r\n{}", form);
+        assert_eq!(handle(&mut state, &request(&cross)).status, 403);
+        let cross = format!("POST /upload HTTP/1.1\r\nHost: localhost:8787\r\nReferer: http://localhost:8787.attacker.example/\r\n{}", form);
+        assert_eq!(handle(&mut state, &request(&cross)).status, 403);
+        let opaque = format!("POST /extract HTTP/1.1\r\nHost: localhost:8787\r\nOrigin: null\r\n{}", form);
+        assert_eq!(handle(&mut state, &request(&opaque)).status, 403);
+
+        // our own pages, and tools that send neither header
+        let own = format!("POST /extract HTTP/1.1\r\nHost: localhost:8787\r\nReferer: http://localhost:8787/?q=x\r\n{}", form);
+        assert_eq!(handle(&mut state, &request(&own)).status, 303);
+        let curl = format!("POST /extract HTTP/1.1\r\nHost: 127.0.0.1:8787\r\n{}", form);
+        assert_eq!(handle(&mut state, &request(&curl)).status, 303);
+
+        let wide = allowed_hosts(&ServeArgs { bind: "::".parse().unwrap(), port: 9000 });
+        assert_eq!(wide, ["127.0.0.1:9000", "localhost:9000", "[::]:9000"]);
+        // browsers leave out port 80
+        let http = allowed_hosts(&ServeArgs { bind: "192.168.1.5".parse().unwrap(), port: 80 });
+        assert_eq!(http, ["127.0.0.1:80", "127.0.0.1", "localhost:80", "localhost", "192.168.1.5:80", "192.168.1.5"]);
+    }
+
+    #[test]
+    fn slow_requests_run_out_of_time() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        let stream = listener.accept().unwrap().0;
+        let mut late = Deadline { stream, end: Instant::now() };
+        assert_eq!(late.read(&mut [0; 16]).unwrap_err().kind(), io::ErrorKind::TimedOut);
+        let mut on_time = Deadline { end: Instant::now() + Duration::from_secs(5), ..late };
+        assert_eq!(on_time.read(&mut [0; 16]).unwrap(), 16);
+    }
+
+    #[test]
+    fn answers_over_tcp() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let hosts = allowed_hosts(&ServeArgs { bind: IpAddr::V4(Ipv4Addr::LOCALHOST), port: address.port() });
+            let mut state = ServeState::new(std::env::temp_dir().join("justducky-serve-unused.log"), hosts);
+            serve_connection(listener.accept().unwrap().0, &mut state).unwrap();
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(format!("GET / HTTP/1.1\r\nHost: localhost:{}\r\n\r\n", address.port()).as_bytes()).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        server.join().unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n"));
+        assert!(reply.ends_with("<p>No bill file loaded.</p></body></html>\n"));
+    }
+}
@@ -0,0 +1,497 @@
+//! Full-screen front end, built with the `tui` feature: a file picker, a bill list that
+//! filters as you type, the picked bill's details, and a status bar. It works on the same
+//! Session as the menus; the menus are still used on dumb terminals or with --plain.
+use std::io;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use duckbill::duckfile::duckbill::DuckBill;
+use duckbill::duckfile::duckerror::DuckError;
+use duckbill::duckfile::duckmeter::format_amount;
+use m_menu::Console;
+
+use crate::audit::AuditOperation;
+use crate::{bill_row, get_file, Session};
+
+/// Bills moved by page up and page down
+const PAGE: usize = 10;
+
+/// Whether the terminal can show the full-screen interface: both ends are a terminal,
+/// and it isn't a dumb one
+pub fn available() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+        && std::env::var("TERM").map_or(cfg!(windows), |term| !term.is_empty() && term != "dumb")
+}
+
+/// Run the full-screen interface until the operator quits
+pub fn run(session: &mut Session) -> Result<(), DuckError> {
+    let start_dir = std::env::current_dir().unwrap_or_default();
+    let mut app = TuiApp::new(session, &start_dir);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    Ok(result?)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Pane {
+    Files,
+    Bills,
+    Details,
+}
+
+struct TuiApp<'s> {
+    session: &'s mut Session,
+    focus: Pane,
+    /// the folder shown in the file pane, and what's in it
+    dir: PathBuf,
+    entries: Vec<(String, PathBuf)>,
+    file_sel: usize,
+    /// text the bill list is filtered by, and whether it's being typed
+    filter: String,
+    filtering: bool,
+    /// each loaded bill's row in the list and the lowercase text the filter looks through,
+    /// worked out once when the file is loaded
+    rows: Vec<(String, String)>,
+    /// pages in the loaded file, counted once when it's loaded
+    page_count: usize,
+    /// indexes of the bills the filter lets through, and which of them is picked
+    shown: Vec<usize>,
+    bill_sel: usize,
+    detail_scroll: u16,
+    /// the detail pane's lines and which bill they're for, kept until another bill is picked
+    details: Option<(usize, Vec<String>)>,
+    /// the last thing that happened, for the status bar
+    message: String,
+    quit: bool,
+}
+
+impl<'s> TuiApp<'s> {
+    fn new(session: &'s mut Session, dir: &Path) -> TuiApp<'s> {
+        let mut app = TuiApp {
+            session,
+            focus: Pane::Files,
+            dir: dir.to_path_buf(),
+            entries: vec![],
+            file_sel: 0,
+            filter: String::new(),
+            filtering: false,
+            rows: vec![],
+            page_count: 0,
+            shown: vec![],
+            bill_sel: 0,
+            detail_scroll: 0,
+            details: None,
+            message: "Pick a bill file. Tab moves between panes, q quits.".to_string(),
+            quit: false,
+        };
+        app.list_dir();
+        app.index_bills();
+        app.refilter();
+        app.show_picked();
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| draw(frame, self))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// List the folder in the file pane: its parent, then folders, then files
+    fn list_dir(&mut self) {
+        let mut dirs = vec![];
+        let mut files = vec![];
+        if let Ok(read) = std::fs::read_dir(&self.dir) {
+            for entry in read.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_dir() {
+                    dirs.push((format!("{}/", name), entry.path()));
+                } else {
+                    files.push((name, entry.path()));
+                }
+            }
+        }
+        dirs.sort();
+        files.sort();
+        self.entries = self.dir.parent().map(|p| ("../".to_string(), p.to_path_buf())).into_iter()
+            .chain(dirs)
+            .chain(files)
+            .collect();
+        self.file_sel = 0;
+    }
+
+    /// Work out each loaded bill's row and search text, and the file's page count.
+    /// Extracts write new files and leave the loaded one alone, so these only change on a load.
+    fn index_bills(&mut self) {
+        self.rows = self.session.original_bills[..].iter()
+            .map(|bill| (bill_row(bill), bill_text(bill).to_lowercase()))
+            .collect();
+        self.page_count = self.session.original_bills.page_count();
+        self.details = None;
+    }
+
+    /// Work out which bills the filter lets through: those whose row or service address contains it
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.shown = self.rows.iter().enumerate()
+            .filter(|(_, (_, text))| filter.is_empty() || text.contains(&filter))
+            .map(|(i, _)| i)
+            .collect();
+        self.bill_sel = self.bill_sel.min(self.shown.len().saturating_sub(1));
+        self.detail_scroll = 0;
+    }
+
+    /// The bill picked in the list, as an index into the loaded bills
+    fn picked(&self) -> Option<usize> {
+        self.shown.get(self.bill_sel).copied()
+    }
+
+    fn handle(&mut self, key: KeyEvent) {
+        self.handle_key(key);
+        self.show_picked();
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if self.filtering {
+            match key.code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => { self.filter.pop(); },
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                KeyCode::Enter => self.filtering = false,
+                _ => return,
+            }
+            self.refilter();
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Tab => self.focus = match self.focus {
+                Pane::Files => Pane::Bills,
+                Pane::Bills => Pane::Details,
+                Pane::Details => Pane::Files,
+            },
+            KeyCode::BackTab => self.focus = match self.focus {
+                Pane::Files => Pane::Details,
+                Pane::Bills => Pane::Files,
+                Pane::Details => Pane::Bills,
+            },
+            _ => match self.focus {
+                Pane::Files => self.handle_files(key.code),
+                Pane::Bills => self.handle_bills(key.code),
+                Pane::Details => self.handle_details(key.code),
+            },
+        }
+    }
+
+    fn handle_files(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Up => self.file_sel = self.file_sel.saturating_sub(1),
+            KeyCode::Down => self.file_sel = (self.file_sel + 1).min(self.entries.len().saturating_sub(1)),
+            KeyCode::Backspace => {
+                if let Some(parent) = self.dir.parent() {
+                    self.dir = parent.to_path_buf();
+                    self.list_dir();
+                }
+            }
+            KeyCode::Enter => {
+                let Some((_, path)) = self.entries.get(self.file_sel).cloned() else {
+                    return;
+                };
+                if path.is_dir() {
+                    self.dir = path;
+                    self.list_dir();
+                } else {
+                    self.load(&path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn load(&mut self, path: &Path) {
+        let mut shown = vec![];
+        let loaded = get_file(&mut Console::new(io::empty(), &mut shown), path);
+        match loaded {
+            Ok(bills) => {
+                self.message = format!("{} bills loaded from {}", bills.get_bill_count(), path.display());
+                self.session.load(path, bills);
+                self.index_bills();
+                self.filter.clear();
+                self.bill_sel = 0;
+                self.refilter();
+                self.focus = Pane::Bills;
+            }
+            Err(_) => self.message = last_line(&shown).unwrap_or_else(|| format!("{} isn't a bill file", path.display())),
+        }
+    }
+
+    fn handle_bills(&mut self, code: KeyCode) {
+        let last = self.shown.len().saturating_sub(1);
+        match code {
+            KeyCode::Up => self.bill_sel = self.bill_sel.saturating_sub(1),
+            KeyCode::Down => self.bill_sel = (self.bill_sel + 1).min(last),
+            KeyCode::PageUp => self.bill_sel = self.bill_sel.saturating_sub(PAGE),
+            KeyCode::PageDown => self.bill_sel = (self.bill_sel + PAGE).min(last),
+            KeyCode::Home => self.bill_sel = 0,
+            KeyCode::End => self.bill_sel = last,
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Enter => self.focus = Pane::Details,
+            KeyCode::Char(c) => self.bill_action(c),
+            _ => return,
+        }
+        self.detail_scroll = 0;
+    }
+
+    fn handle_details(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Up => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            KeyCode::Down => self.detail_scroll = self.detail_scroll.saturating_add(1),
+            KeyCode::PageUp => self.detail_scroll = self.detail_scroll.saturating_sub(PAGE as u16),
+            KeyCode::PageDown => self.detail_scroll = self.detail_scroll.saturating_add(PAGE as u16),
+            KeyCode::Char(c) => self.bill_action(c),
+            _ => {}
+        }
+    }
+
+    /// Act on the picked bill: mark it as the (s)tart or (e)nd of a range, extract
+    /// (o)nly it, or e(x)tract the marked range
+    fn bill_action(&mut self, c: char) {
+        let Some(bill_idx) = self.picked() else {
+            self.message = "No bills to act on.".to_string();
+            return;
+        };
+        match c {
+            's' => {
+                self.session.range_start = Some(bill_idx);
+                self.message = format!("Bill {} marks the start of the range.", bill_idx + 1);
+            }
+            'e' => {
+                self.session.range_end = Some(bill_idx);
+                self.message = format!("Bill {} marks the end of the range.", bill_idx + 1);
+            }
+            'o' => {
+                let sel = self.session.original_bills[bill_idx..=bill_idx].to_vec();
+                self.extract(sel, AuditOperation::Single);
+            }
+            'x' => match (self.session.range_start, self.session.range_end) {
+                (Some(start), Some(end)) => {
+                    let (start, end) = (start.min(end), start.max(end));
+                    let sel = self.session.original_bills[start..=end].to_vec();
+                    self.extract(sel, AuditOperation::Range);
+                    self.session.range_start = None;
+                    self.session.range_end = None;
+                }
+                _ => self.message = "Mark the start and end of a range first, with s and e.".to_string(),
+            },
+            _ => {}
+        }
+    }
+
+    /// Write bills to a processed file, without a stamp, showing how it went in the status bar
+    fn extract(&mut self, sel: Vec<DuckBill>, operation: AuditOperation) {
+        let mut shown = vec![];
+        let result = self.session.create_output(&mut Console::new(io::empty(), &mut shown), sel, operation);
+        self.message = match result {
+            Ok(()) => last_line(&shown).unwrap_or_default(),
+            Err(e) => e.to_string(),
+        };
+    }
+
+    /// The status bar: the loaded file and its counts, the marked range, and the pending output file
+    fn status(&self) -> String {
+        let Some(path) = &self.session.loaded_path else {
+            return "No bill file loaded".to_string();
+        };
+        let bills = &self.session.original_bills;
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let mark = |m: Option<usize>| m.map(|i| (i + 1).to_string()).unwrap_or_else(|| "-".to_string());
+        let output = match self.session.output_filename.as_os_str().is_empty() {
+            true => "none yet".to_string(),
+            false => self.session.output_filename.display().to_string(),
+        };
+        format!("{}: {} bills, {} pages, {} shown | range {} to {} | output: {}",
+                name, bills.get_bill_count(), self.page_count, self.shown.len(),
+                mark(self.session.range_start), mark(self.session.range_end), output)
+    }
+
+    /// Work out what the detail pane shows, if a different bill has been picked since last time
+    fn show_picked(&mut self) {
+        let picked = self.picked();
+        if self.details.as_ref().map(|(idx, _)| *idx) != picked {
+            self.details = picked.map(|idx| (idx, self.bill_details(idx)));
+        }
+    }
+
+    /// What the detail pane shows for a bill
+    fn bill_details(&self, bill_idx: usize) -> Vec<String> {
+        let bill = &self.session.original_bills[bill_idx];
+        let mut lines = vec![
+            format!("Account ID:      {}", bill.get_account_id()),
+            format!("Bill number:     {}", bill.get_bill_number()),
+            format!("Name:            {}", bill.get_name().unwrap_or_default()),
+            format!("Service address: {}", bill.get_service_address().join(", ")),
+            format!("Mailing address: {}", bill.get_mailing_address().join(", ")),
+            format!("Amount due:      {}", bill.get_amount_due().map(format_amount).unwrap_or_default()),
+            format!("Size:            {} bytes", bill.get_raw().len()),
+            String::new(),
+        ];
+        lines.extend(bill.get_meter_readings().iter().map(|r| r.to_line()));
+        lines.push(String::new());
+        lines.extend(bill.get_plain_text());
+        lines
+    }
+}
+
+/// Everything the bill filter looks through
+fn bill_text(bill: &DuckBill) -> String {
+    format!("{} {}", bill_row(bill), bill.get_service_address().join(" "))
+}
+
+/// The last non-blank line written to a captured console
+fn last_line(shown: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(shown).lines().rev().find(|l| !l.trim().is_empty()).map(|l| l.trim().to_string())
+}
+
+fn draw(frame: &mut Frame, app: &TuiApp) {
+    let [main, filter_area, status_area] = Layout::vertical([
+        Constraint::Min(3), Constraint::Length(1), Constraint::Length(2),
+    ]).areas(frame.area());
+    let [files_area, bills_area, detail_area] = Layout::horizontal([
+        Constraint::Percentage(20), Constraint::Percentage(45), Constraint::Percentage(35),
+    ]).areas(main);
+
+    let pane = |title: String, pane: Pane| {
+        let block = Block::default().borders(Borders::ALL).title(title);
+        match app.focus == pane {
+            true => block.border_style(Style::default().add_modifier(Modifier::BOLD)),
+            false => block,
+        }
+    };
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+    let files = List::new(app.entries.iter().map(|(label, _)| label.as_str()))
+        .block(pane(app.dir.display().to_string(), Pane::Files))
+        .highlight_style(highlight);
+    let mut files_state = ListState::default().with_selected(Some(app.file_sel));
+    frame.render_stateful_widget(files, files_area, &mut files_state);
+
+    let bills = &app.session.original_bills;
+    let rows = app.shown.iter().map(|&i| {
+        let mark = match (app.session.range_start == Some(i), app.session.range_end == Some(i)) {
+            (true, _) => "> ",
+            (_, true) => "< ",
+            _ => "  ",
+        };
+        format!("{}{:>5} {}", mark, i + 1, app.rows[i].0)
+    });
+    let bill_list = List::new(rows)
+        .block(pane(format!("Bills ({} of {})", app.shown.len(), bills.get_bill_count()), Pane::Bills))
+        .highlight_style(highlight);
+    let mut bills_state = ListState::default().with_selected(app.picked().map(|_| app.bill_sel));
+    frame.render_stateful_widget(bill_list, bills_area, &mut bills_state);
+
+    let detail_title = match app.picked() {
+        Some(i) => format!("Bill {} of {}", i + 1, bills.get_bill_count()),
+        None => "Bill".to_string(),
+    };
+    let detail_lines = app.details.iter().flat_map(|(_, lines)| lines.iter().map(|l| Line::from(l.as_str())));
+    let details = Paragraph::new(detail_lines.collect::<Vec<_>>())
+        .block(pane(detail_title, Pane::Details))
+        .scroll((app.detail_scroll, 0));
+    frame.render_widget(details, detail_area);
+
+    let filter = match (app.filtering, app.filter.is_empty()) {
+        (true, _) => format!("Filter: {}_  (enter keeps it, esc clears it)", app.filter),
+        (false, true) => "/ filter  s/e mark start/end  x extract range  o extract bill  tab next pane  q quit".to_string(),
+        (false, false) => format!("Filter: {}  (/ to change)", app.filter),
+    };
+    frame.render_widget(Paragraph::new(filter), filter_area);
+    frame.render_widget(Paragraph::new(vec![
+        Line::styled(app.status(), Style::default().add_modifier(Modifier::REVERSED)),
+        Line::from(app.message.as_str()),
+    ]), status_area);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use crate::output::OutputPolicy;
+
+    fn press(app: &mut TuiApp, keys: &[KeyCode]) {
+        for &code in keys {
+            app.handle(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    fn typed(text: &str) -> Vec<KeyCode> {
+        text.chars().map(KeyCode::Char).collect()
+    }
+
+    #[test]
+    fn load_filter_mark_and_extract() {
+        let dir = std::env::temp_dir().join(format!("justducky-tui-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("older")).unwrap();
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("../duckbill/test_data/anon_bill_file_4.dat");
+        std::fs::copy(sample, dir.join("april.dat")).unwrap();
+        let mut session = Session::new(OutputPolicy::default(), dir.join("audit.log"), dir.join("checkpoints"));
+
+        let mut app = TuiApp::new(&mut session, &dir);
+        let labels: Vec<&str> = app.entries.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["../", "older/", "april.dat"]);
+        press(&mut app, &[KeyCode::Down, KeyCode::Down, KeyCode::Enter]);
+        assert_eq!((app.focus, app.shown.len()), (Pane::Bills, 4));
+        assert!(app.rows[2].1.contains("name three three33"));
+
+        press(&mut app, &[KeyCode::Char('/')]);
+        press(&mut app, &typed("name n"));
+        assert_eq!(app.shown, [0, 1]);
+        press(&mut app, &[KeyCode::Esc, KeyCode::Char('x')]);
+        assert_eq!(app.message, "Mark the start and end of a range first, with s and e.");
+        press(&mut app, &[KeyCode::Down, KeyCode::Char('s'), KeyCode::End, KeyCode::Char('e')]);
+        assert_eq!((app.session.range_start, app.session.range_end), (Some(1), Some(3)));
+        assert_eq!(app.details.as_ref().map(|(idx, _)| *idx), Some(3));
+
+        let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("april.dat: 4 bills, 4 pages, 4 shown | range 2 to 4 | output: none yet"));
+        assert!(screen.contains("Bill 4 of 4"));
+        assert!(screen.contains("Account ID:      52-4444444-1"));
+
+        press(&mut app, &[KeyCode::Char('x')]);
+        assert!(app.message.starts_with("Your processed file is ready: "));
+        let output = get_file(&mut Console::new(io::empty(), io::sink()), &dir.join("april.dat.DUCKED")).unwrap();
+        assert_eq!(output.get_bill_count(), 3);
+        assert_eq!(app.session.range_start, None);
+
+        press(&mut app, &[KeyCode::Char('q')]);
+        assert!(app.quit);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+//! where menus and prompts read answers from and write to. usually stdin and stdout,
+//! but any BufRead and Write will do, so a whole session can be played from a script
+//! and what it showed compared afterwards.
+use std::io;
+use std::io::{BufRead, Write};
+
+use crate::style::MenuStyle;
+
+pub struct Console<'a> {
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+    echo: bool,
+    style: MenuStyle,
+}
+
+impl<'a> Console<'a> {
+    pub fn new(input: impl BufRead + 'a, output: impl Write + 'a) -> Console<'a> {
+        Console {
+            input: Box::new(input),
+            output: Box::new(output),
+            echo: false,
+            style: MenuStyle::plain(),
+        }
+    }
+
+    /// draw menus on this console with `style` instead of plain text
+    pub fn with_style(mut self, style: MenuStyle) -> Console<'a> {
+        self.style = style;
+        self
+    }
+
+    pub fn get_style(&self) -> MenuStyle {
+        self.style
+    }
+
+    /// write each line read back to the output, so a scripted session's transcript
+    /// reads like what a person at the terminal would have seen
+    pub fn echo_input(mut self) -> Console<'a> {
+        self.echo = true;
+        self
+    }
+
+    /// reads one line, without its line ending. anything written so far is flushed first.
+    /// returns None at the end of the input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if self.echo {
+            writeln!(self.output, "{}", line)?;
+        }
+        Ok(Some(line))
+    }
+}
+
+impl Console<'static> {
+    /// the terminal: stdin and stdout, styled to suit stdout
+    pub fn stdio() -> Console<'static> {
+        Console::new(io::stdin().lock(), io::stdout()).with_style(MenuStyle::detect())
+    }
+}
+
+impl Write for Console<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_and_echoes() {
+        let mut shown = vec![];
+        {
+            let mut con = Console::new("one\r\ntwo".as_bytes(), &mut shown).echo_input();
+            write!(con, "> ").unwrap();
+            assert_eq!(con.read_line().unwrap().as_deref(), Some("one"));
+            write!(con, "> ").unwrap();
+            assert_eq!(con.read_line().unwrap().as_deref(), Some("two"));
+            assert_eq!(con.read_line().unwrap(), None);
+        }
+        assert_eq!(String::from_utf8(shown).unwrap(), "> one\n> two\n");
+    }
+}
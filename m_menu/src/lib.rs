@@ -1,61 +1,486 @@
-use std::collections::{BTreeMap};
 use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
 
-pub struct MMenu {
-    menu: BTreeMap<String, MenuEntry>,
+pub mod console;
+pub mod picker;
+pub mod prompt;
+pub mod style;
+pub use console::Console;
+pub use picker::Picker;
+pub use prompt::{prompt_parse, Prompt};
+pub use style::MenuStyle;
+
+/// reserved selector that leaves a submenu
+pub const BACK: &str = "b";
+/// reserved selector that returns to the top menu
+pub const HOME: &str = "h";
+/// reserved selector that leaves the menu altogether
+pub const QUIT: &str = "q";
+
+/// why a menu operation failed
+#[derive(Debug, PartialEq, Clone)]
+pub enum MMenuError {
+    /// no entry has this selector
+    NoSuchSelector(String),
+}
+
+impl Display for MMenuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MMenuError::NoSuchSelector(selector) => write!(f, "the menu has no entry {}", selector),
+        }
+    }
+}
+
+impl std::error::Error for MMenuError {}
+
+/// where the menu goes after an action has run
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MenuOutcome {
+    /// show the same menu again
+    Stay,
+    /// go up one level
+    Back,
+    /// go to the top menu
+    Home,
+    /// leave the menu loop
+    Quit,
 }
 
-struct MenuEntry {
+/// something to run when an entry is chosen. gets the caller's state and the menu's console.
+pub type MenuAction<S, E> = Box<dyn FnMut(&mut S, &mut Console) -> Result<MenuOutcome, E>>;
+/// a rule deciding from the caller's state whether an entry can be chosen
+pub type MenuPredicate<S> = Box<dyn Fn(&S) -> bool>;
+/// makes an entry's text from the caller's state
+pub type MenuLabel<S> = Box<dyn Fn(&S) -> String>;
+
+pub struct MMenu<S = (), E = io::Error> {
+    title: String,
+    /// entries, headers and separators, in the order they were added
+    items: Vec<MenuItem<S, E>>,
+    /// list disabled entries, dimmed, instead of leaving them out
+    show_disabled: bool,
+    /// ask this question before quitting while the predicate holds
+    confirm_quit: Option<(String, MenuPredicate<S>)>,
+}
+
+enum MenuItem<S, E> {
+    Entry(String, MenuEntry<S, E>),
+    /// a header over the entries after it
+    Section(String),
+    Separator,
+}
+
+/// what became of a choice
+#[derive(Debug, PartialEq)]
+enum Chosen {
+    Done(MenuOutcome),
+    /// the choice was refused, for the reason given
+    Refused(String),
+}
+
+/// a line of the menu as it will be shown
+enum Line {
+    Entry(String, bool),
+    Section(String),
+    Separator,
+}
+
+pub struct MenuEntry<S = (), E = io::Error> {
     displayed: String,
     enabled: bool,
+    enabled_when: Option<MenuPredicate<S>>,
+    label_with: Option<MenuLabel<S>>,
+    /// shown next to the entry while it's disabled
+    unavailable_because: Option<String>,
+    kind: EntryKind<S, E>,
 }
 
-impl MMenu {
-    pub fn new() -> MMenu {
+enum EntryKind<S, E> {
+    /// just a label; choosing it is up to the caller
+    Label,
+    Action(MenuAction<S, E>),
+    Submenu(MMenu<S, E>),
+}
+
+impl<S, E> MenuEntry<S, E> {
+    fn new(displayed: &str, enabled: bool, kind: EntryKind<S, E>) -> MenuEntry<S, E> {
+        MenuEntry {
+            displayed: String::from(displayed),
+            enabled,
+            enabled_when: None,
+            label_with: None,
+            unavailable_because: None,
+            kind,
+        }
+    }
+
+    /// only allow this entry while the predicate holds for the caller's state
+    pub fn enabled_when(&mut self, predicate: impl Fn(&S) -> bool + 'static) -> &mut Self {
+        self.enabled_when = Some(Box::new(predicate));
+        self
+    }
+
+    /// build the displayed text from the caller's state each time the menu is shown
+    pub fn label_with(&mut self, label: impl Fn(&S) -> String + 'static) -> &mut Self {
+        self.label_with = Some(Box::new(label));
+        self
+    }
+
+    /// say why the entry is disabled, e.g. "load a file first". shown beside it when
+    /// disabled entries are listed, and when it's chosen anyway.
+    pub fn unavailable_because(&mut self, reason: &str) -> &mut Self {
+        self.unavailable_because = Some(String::from(reason));
+        self
+    }
+
+    /// whether the entry can be chosen given this state
+    fn is_enabled(&self, state: &S) -> bool {
+        self.enabled && self.enabled_when.as_ref().is_none_or(|p| p(state))
+    }
+
+    fn label(&self, state: &S) -> String {
+        match &self.label_with {
+            Some(label) => label(state),
+            None => self.displayed.clone(),
+        }
+    }
+}
+
+impl<S, E> MMenu<S, E> {
+    pub fn new() -> MMenu<S, E> {
+        MMenu::titled("")
+    }
+
+    /// makes a menu with a title shown above it by run()
+    pub fn titled(title: &str) -> MMenu<S, E> {
         MMenu {
-            menu: BTreeMap::new(),
+            title: String::from(title),
+            items: vec![],
+            show_disabled: false,
+            confirm_quit: None,
         }
     }
 
-    /// adds an item to the menu.
-    /// if an entry with the same selector exists, updates instead
+    /// before quitting, ask `question` and only quit on yes, whenever the predicate holds;
+    /// for example while there's work that would be lost. pressing enter answers no,
+    /// but end of input still quits, since nobody is left to answer
+    pub fn confirm_quit_when(&mut self, question: &str, predicate: impl Fn(&S) -> bool + 'static) {
+        self.confirm_quit = Some((String::from(question), Box::new(predicate)));
+    }
+
+    /// list disabled entries, dimmed and with their reasons, rather than hiding them
+    pub fn show_disabled(&mut self, show: bool) {
+        self.show_disabled = show;
+    }
+
+    /// adds a header over the entries added after it.
+    /// it's only shown while at least one of them is
+    pub fn add_section(&mut self, title: &str) {
+        self.items.push(MenuItem::Section(String::from(title)));
+    }
+
+    /// adds a line between the entries before and after it
+    pub fn add_separator(&mut self) {
+        self.items.push(MenuItem::Separator);
+    }
+
+    /// adds an item to the menu. entries are shown in the order they're added.
+    /// if an entry with the same selector exists, updates it in place instead
     pub fn add_entry(&mut self, selector: &str, displayed: &str, enabled: bool) {
-        self.menu.insert(selector.to_lowercase(), MenuEntry {
-            displayed: String::from(displayed),
-            enabled
-        });
+        self.insert(selector, MenuEntry::new(displayed, enabled, EntryKind::Label));
+    }
+
+    /// adds an item that runs an action when chosen.
+    /// if an entry with the same selector exists, replaces it
+    pub fn add_action(&mut self, selector: &str, displayed: &str,
+                      action: impl FnMut(&mut S, &mut Console) -> Result<MenuOutcome, E> + 'static) -> &mut MenuEntry<S, E> {
+        self.insert(selector, MenuEntry::new(displayed, true, EntryKind::Action(Box::new(action))))
+    }
+
+    /// adds an item that opens another menu when chosen.
+    /// the submenu takes the entry's text as its title if it has none.
+    pub fn add_submenu(&mut self, selector: &str, displayed: &str, mut submenu: MMenu<S, E>) -> &mut MenuEntry<S, E> {
+        if submenu.title.is_empty() {
+            submenu.title = String::from(displayed);
+        }
+        self.insert(selector, MenuEntry::new(displayed, true, EntryKind::Submenu(submenu)))
+    }
+
+    fn insert(&mut self, selector: &str, entry: MenuEntry<S, E>) -> &mut MenuEntry<S, E> {
+        let selector = selector.to_lowercase();
+        debug_assert!(![BACK, HOME, QUIT].contains(&selector.as_str()), "{} is a reserved selector", selector);
+        let at = match self.position(&selector) {
+            Some(at) => {
+                self.items[at] = MenuItem::Entry(selector, entry);
+                at
+            }
+            None => {
+                self.items.push(MenuItem::Entry(selector, entry));
+                self.items.len() - 1
+            }
+        };
+        match &mut self.items[at] {
+            MenuItem::Entry(_, entry) => entry,
+            _ => unreachable!("entry just inserted"),
+        }
+    }
+
+    /// where the entry with this (lowercased) selector is in the list
+    fn position(&self, selector: &str) -> Option<usize> {
+        self.items.iter().position(|item| matches!(item, MenuItem::Entry(sel, _) if sel == selector))
+    }
+
+    fn entry(&self, selector: &str) -> Option<&MenuEntry<S, E>> {
+        match self.position(&selector.to_lowercase()).map(|at| &self.items[at]) {
+            Some(MenuItem::Entry(_, entry)) => Some(entry),
+            _ => None,
+        }
+    }
+
+    fn entry_mut(&mut self, selector: &str) -> Option<&mut MenuEntry<S, E>> {
+        match self.position(&selector.to_lowercase()).map(|at| &mut self.items[at]) {
+            Some(MenuItem::Entry(_, entry)) => Some(entry),
+            _ => None,
+        }
     }
 
     /// validates a choice.
     /// a choice is valid if it exists and is enabled
     pub fn valid_choice(&self, selection: &str) -> bool {
-        match self.menu.get(&selection.to_lowercase()) {
+        match self.entry(selection) {
             Some(entry) => entry.enabled,
             None => false
         }
     }
 
+    /// validates a choice against the caller's state, applying any enabled_when rule
+    pub fn valid_choice_for(&self, selection: &str, state: &S) -> bool {
+        match self.entry(selection) {
+            Some(entry) => entry.is_enabled(state),
+            None => false
+        }
+    }
+
 
     /// change the enabledness of a choice
-    pub fn set_choice_avail(&mut self, selection:&str, enabled: bool) -> Result<(),()> {
-        match self.menu.get_mut(&selection.to_lowercase()) {
+    pub fn set_choice_avail(&mut self, selection:&str, enabled: bool) -> Result<(), MMenuError> {
+        match self.entry_mut(selection) {
             Some(entry) => {
                 entry.enabled = enabled;
                 Ok(())
             },
-            None => Err(())
+            None => Err(MMenuError::NoSuchSelector(selection.to_string()))
+        }
+    }
+
+    /// the menu as shown for this state, in plain text: entries with their current labels,
+    /// under their section headers
+    pub fn render(&self, state: &S) -> String {
+        self.render_styled(state, &MenuStyle::plain())
+    }
+
+    /// the menu as shown for this state, drawn in `style`. disabled entries are left out
+    /// unless show_disabled is set, in which case they're dimmed and give their reasons.
+    pub fn render_styled(&self, state: &S, style: &MenuStyle) -> String {
+        let lines = self.lines(state);
+        let widest = lines.iter()
+            .map(|line| match line {
+                Line::Entry(text, _) | Line::Section(text) => text.chars().count(),
+                Line::Separator => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .min(style.width);
+
+        let mut shown: Vec<String> = vec![];
+        for line in lines {
+            match line {
+                Line::Entry(text, true) => shown.push(style.fit(&text)),
+                Line::Entry(text, false) => shown.push(style.dim(&style.fit(&text))),
+                Line::Section(title) => {
+                    if !shown.is_empty() {
+                        shown.push(String::new());
+                    }
+                    shown.push(style.bold(&style.fit(&title)));
+                }
+                Line::Separator => shown.push(style.dim(&"-".repeat(widest))),
+            }
+        }
+        shown.join("\n")
+    }
+
+    /// the lines to show for this state, without sections that have no entries showing
+    /// or separators that have nothing to separate
+    fn lines(&self, state: &S) -> Vec<Line> {
+        let mut lines: Vec<Line> = vec![];
+        for item in &self.items {
+            match item {
+                MenuItem::Entry(sel, entry) if entry.is_enabled(state) => {
+                    lines.push(Line::Entry(format!("{} {}", sel, entry.label(state)), true));
+                }
+                MenuItem::Entry(sel, entry) if self.show_disabled => {
+                    let text = match &entry.unavailable_because {
+                        Some(reason) => format!("{} {} ({})", sel, entry.label(state), reason),
+                        None => format!("{} {}", sel, entry.label(state)),
+                    };
+                    lines.push(Line::Entry(text, false));
+                }
+                MenuItem::Entry(..) => {}
+                MenuItem::Section(title) => lines.push(Line::Section(title.clone())),
+                MenuItem::Separator => lines.push(Line::Separator),
+            }
+        }
+
+        // walking backwards, a section is kept only if an entry came after it
+        let mut entry_after = false;
+        let mut kept: Vec<Line> = vec![];
+        for line in lines.into_iter().rev() {
+            match line {
+                Line::Entry(..) => entry_after = true,
+                Line::Section(_) if !entry_after => continue,
+                Line::Section(_) => entry_after = false,
+                Line::Separator => {
+                    // nothing after it yet, or another separator right after it
+                    if matches!(kept.last(), None | Some(Line::Separator)) {
+                        continue;
+                    }
+                }
+            }
+            kept.push(line);
+        }
+        kept.reverse();
+        while matches!(kept.first(), Some(Line::Separator)) {
+            kept.remove(0);
+        }
+        kept
+    }
+
+    /// follow a path of submenu selectors down from this menu
+    fn submenu_at(&mut self, path: &[String]) -> &mut MMenu<S, E> {
+        let mut menu = self;
+        for sel in path {
+            menu = match menu.entry_mut(sel).map(|e| &mut e.kind) {
+                Some(EntryKind::Submenu(sub)) => sub,
+                _ => unreachable!("menu path only holds submenus"),
+            };
+        }
+        menu
+    }
+
+    /// act on one choice made in the menu at `path`, updating the path as the menu moves
+    fn choose(&mut self, path: &mut Vec<String>, choice: &str, state: &mut S, con: &mut Console) -> Result<Chosen, E> {
+        let choice = choice.trim().to_lowercase();
+        let outcome = match choice.as_str() {
+            QUIT => MenuOutcome::Quit,
+            BACK if !path.is_empty() => MenuOutcome::Back,
+            HOME if !path.is_empty() => MenuOutcome::Home,
+            "" => return Ok(Chosen::Refused(String::from("Please choose one of the options."))),
+            _ => {
+                let menu = self.submenu_at(path);
+                let Some(entry) = menu.entry_mut(&choice) else {
+                    return Ok(Chosen::Refused(String::from("That isn't one of the options.")));
+                };
+                if !entry.is_enabled(state) {
+                    return Ok(Chosen::Refused(match &entry.unavailable_because {
+                        Some(reason) => format!("That option isn't available right now: {}.", reason),
+                        None => String::from("That option isn't available right now."),
+                    }));
+                }
+                match &mut entry.kind {
+                    EntryKind::Label => return Ok(Chosen::Refused(String::from("That option can't be chosen here."))),
+                    EntryKind::Action(action) => action(state, con)?,
+                    EntryKind::Submenu(_) => {
+                        path.push(choice);
+                        MenuOutcome::Stay
+                    }
+                }
+            }
+        };
+        match outcome {
+            MenuOutcome::Back => { path.pop(); },
+            MenuOutcome::Home => path.clear(),
+            MenuOutcome::Stay | MenuOutcome::Quit => {}
+        }
+        Ok(Chosen::Done(outcome))
+    }
+
+    /// check with the user before quitting, if the menu was set up to
+    fn quit_confirmed(&self, state: &S, con: &mut Console) -> io::Result<bool> {
+        match &self.confirm_quit {
+            Some((question, predicate)) if predicate(state) => {
+                Ok(Prompt::new(question).default("n").yes_no(con)? != Some(false))
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// show the menu on the terminal and carry out choices until the user quits.
+    /// choosing a submenu opens it; b goes back up, h goes to the top, and q quits.
+    /// anything that isn't an enabled choice is refused and asked for again.
+    pub fn run(&mut self, state: &mut S) -> Result<(), E> where E: From<io::Error> {
+        self.run_with(state, &mut Console::stdio())
+    }
+
+    /// like run(), but reading choices from and showing the menu on the given console,
+    /// in the console's style
+    pub fn run_with(&mut self, state: &mut S, con: &mut Console) -> Result<(), E> where E: From<io::Error> {
+        let style = con.get_style();
+        let mut path: Vec<String> = vec![];
+        loop {
+            let menu = self.submenu_at(&path);
+            if !menu.title.is_empty() {
+                let title = style.fit(&menu.title);
+                writeln!(con, "\n{}", style.bold(&title))?;
+                writeln!(con, "{}", "=".repeat(title.chars().count()))?;
+            }
+            writeln!(con, "{}", menu.render_styled(state, &style))?;
+            if !path.is_empty() {
+                writeln!(con, "{} Back", BACK)?;
+                writeln!(con, "{} Main menu", HOME)?;
+            }
+            writeln!(con, "{} Quit", QUIT)?;
+
+            loop {
+                write!(con, "Select an option: ")?;
+                // the end of the input quits; there's no one left to ask
+                let Some(choice) = con.read_line()? else {
+                    return Ok(());
+                };
+                writeln!(con)?;
+
+                match self.choose(&mut path, &choice, state, con)? {
+                    Chosen::Refused(reason) => writeln!(con, "{}", reason)?,
+                    Chosen::Done(MenuOutcome::Quit) if choice.trim().eq_ignore_ascii_case(QUIT) => {
+                        if self.quit_confirmed(state, con)? {
+                            return Ok(());
+                        }
+                        break;
+                    }
+                    Chosen::Done(MenuOutcome::Quit) => return Ok(()),
+                    Chosen::Done(_) => break,
+                }
+            }
         }
     }
 }
 
+impl<S, E> Default for MMenu<S, E> {
+    fn default() -> Self {
+        MMenu::new()
+    }
+}
 
-impl Display for MMenu {
+impl<S, E> Display for MMenu<S, E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut items: Vec<String> = vec![];
 
-        for (sel, entry) in self.menu.iter() {
-            if entry.enabled {
-                items.push(format!("{} {}", &sel, &entry.displayed))
+        for item in self.items.iter() {
+            if let MenuItem::Entry(sel, entry) = item {
+                if entry.enabled {
+                    items.push(format!("{} {}", &sel, &entry.displayed))
+                }
             }
         }
 
@@ -67,8 +492,210 @@ impl Display for MMenu {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn quiet() -> Console<'static> {
+        Console::new(io::empty(), io::sink())
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        count: u32,
+        loaded: bool,
+    }
+
+    fn counter_menu() -> MMenu<Counter> {
+        let mut sub = MMenu::new();
+        sub.add_action("1", "Add one", |c: &mut Counter, _: &mut Console| { c.count += 1; Ok(MenuOutcome::Stay) });
+        sub.add_action("2", "Add ten and go home", |c: &mut Counter, _: &mut Console| { c.count += 10; Ok(MenuOutcome::Home) });
+
+        let mut menu = MMenu::titled("Main");
+        menu.add_action("1", "Load", |c: &mut Counter, _: &mut Console| { c.loaded = true; Ok(MenuOutcome::Stay) });
+        menu.add_submenu("2", "Counting", sub).enabled_when(|c| c.loaded);
+        menu.add_action("3", "Reset", |c: &mut Counter, _: &mut Console| { c.count = 0; Ok(MenuOutcome::Stay) })
+            .label_with(|c| format!("Reset (now {})", c.count));
+        menu
+    }
+
     #[test]
-    fn m_menu_dummytest() {
-        assert_eq!(true, true);
+    fn predicates_and_labels_follow_state() {
+        let menu = counter_menu();
+        let mut state = Counter::default();
+        assert!(!menu.valid_choice_for("2", &state));
+        assert_eq!(menu.render(&state), "1 Load\n3 Reset (now 0)");
+
+        state.loaded = true;
+        state.count = 4;
+        assert!(menu.valid_choice_for("2", &state));
+        assert_eq!(menu.render(&state), "1 Load\n2 Counting\n3 Reset (now 4)");
+    }
+
+    #[test]
+    fn navigation() {
+        let mut menu = counter_menu();
+        let mut state = Counter::default();
+        let mut path = vec![];
+        let choose = |menu: &mut MMenu<Counter>, path: &mut Vec<String>, choice: &str, state: &mut Counter| {
+            menu.choose(path, choice, state, &mut quiet()).unwrap()
+        };
+
+        assert_eq!(choose(&mut menu, &mut path, "2", &mut state), Chosen::Refused("That option isn't available right now.".to_string()));
+        assert_eq!(choose(&mut menu, &mut path, "1", &mut state), Chosen::Done(MenuOutcome::Stay));
+        assert_eq!(choose(&mut menu, &mut path, "2\n", &mut state), Chosen::Done(MenuOutcome::Stay));
+        assert_eq!(path, ["2"]);
+        assert_eq!(menu.submenu_at(&path).title, "Counting");
+
+        choose(&mut menu, &mut path, "1", &mut state);
+        choose(&mut menu, &mut path, "1", &mut state);
+        assert_eq!((state.count, path.len()), (2, 1));
+        assert_eq!(choose(&mut menu, &mut path, "B", &mut state), Chosen::Done(MenuOutcome::Back));
+        assert!(path.is_empty());
+
+        choose(&mut menu, &mut path, "2", &mut state);
+        assert_eq!(choose(&mut menu, &mut path, "2", &mut state), Chosen::Done(MenuOutcome::Home));
+        assert_eq!((state.count, path.len()), (12, 0));
+
+        // back and home mean nothing at the top
+        assert_eq!(choose(&mut menu, &mut path, "b", &mut state), Chosen::Refused("That isn't one of the options.".to_string()));
+        assert_eq!(choose(&mut menu, &mut path, "", &mut state), Chosen::Refused("Please choose one of the options.".to_string()));
+        assert_eq!(choose(&mut menu, &mut path, "q", &mut state), Chosen::Done(MenuOutcome::Quit));
+    }
+
+    #[test]
+    fn scripted_session() {
+        let mut menu = counter_menu();
+        menu.confirm_quit_when("Quit without saving the count? (y/n): ", |c| c.count > 0);
+        let mut state = Counter::default();
+        let mut shown = vec![];
+        {
+            let script = "2\n1\n2\n1\nb\n6\n\nq\nn\nq\n\nq\ny\n";
+            let mut con = Console::new(script.as_bytes(), &mut shown).echo_input();
+            menu.run_with(&mut state, &mut con).unwrap();
+        }
+        assert_eq!(String::from_utf8(shown).unwrap(), "
+Main
+====
+1 Load
+3 Reset (now 0)
+q Quit
+Select an option: 2
+
+That option isn't available right now.
+Select an option: 1
+
+
+Main
+====
+1 Load
+2 Counting
+3 Reset (now 0)
+q Quit
+Select an option: 2
+
+
+Counting
+========
+1 Add one
+2 Add ten and go home
+b Back
+h Main menu
+q Quit
+Select an option: 1
+
+
+Counting
+========
+1 Add one
+2 Add ten and go home
+b Back
+h Main menu
+q Quit
+Select an option: b
+
+
+Main
+====
+1 Load
+2 Counting
+3 Reset (now 1)
+q Quit
+Select an option: 6
+
+That isn't one of the options.
+Select an option: 
+
+Please choose one of the options.
+Select an option: q
+
+Quit without saving the count? (y/n) [n]: n
+
+Main
+====
+1 Load
+2 Counting
+3 Reset (now 1)
+q Quit
+Select an option: q
+
+Quit without saving the count? (y/n) [n]: 
+
+Main
+====
+1 Load
+2 Counting
+3 Reset (now 1)
+q Quit
+Select an option: q
+
+Quit without saving the count? (y/n) [n]: y
+");
+
+        // nobody is left to answer at the end of input, so it quits
+        let mut shown = vec![];
+        menu.run_with(&mut state, &mut Console::new("q\n".as_bytes(), &mut shown)).unwrap();
+        assert!(String::from_utf8(shown).unwrap().ends_with("Quit without saving the count? (y/n) [n]: "));
+    }
+
+    #[test]
+    fn labels_and_availability() {
+        let mut menu: MMenu = MMenu::new();
+        menu.add_entry("1", "One", true);
+        menu.add_entry("2", "Two", false);
+        assert!(menu.valid_choice("1"));
+        assert!(!menu.valid_choice("2"));
+        assert_eq!(menu.set_choice_avail("2", true), Ok(()));
+        let missing = menu.set_choice_avail("9", true).unwrap_err();
+        assert_eq!(missing, MMenuError::NoSuchSelector("9".to_string()));
+        assert_eq!(missing.to_string(), "the menu has no entry 9");
+        assert_eq!(menu.to_string(), "1 One\n2 Two");
+        // plain labels don't do anything by themselves
+        assert_eq!(menu.choose(&mut vec![], "1", &mut (), &mut quiet()).unwrap(), Chosen::Refused("That option can't be chosen here.".to_string()));
+    }
+
+    #[test]
+    fn order_sections_and_disabled_entries() {
+        let mut menu = counter_menu();
+        menu.add_separator();
+        menu.add_section("More");
+        menu.add_entry("10", "Ten", true);
+        menu.add_action("2", "Counting again", |_: &mut Counter, _: &mut Console| Ok(MenuOutcome::Stay))
+            .enabled_when(|c| c.loaded)
+            .unavailable_because("load first");
+        let state = Counter::default();
+        // "10" stays after "3", and replacing "2" keeps its place
+        assert_eq!(menu.render(&state), "1 Load\n3 Reset (now 0)\n---------------\n\nMore\n10 Ten");
+
+        menu.set_choice_avail("10", false).unwrap();
+        assert_eq!(menu.render(&state), "1 Load\n3 Reset (now 0)");
+
+        menu.show_disabled(true);
+        let rule = "-".repeat("2 Counting again (load first)".len());
+        assert_eq!(menu.render(&state), format!("1 Load\n2 Counting again (load first)\n3 Reset (now 0)\n{}\n\nMore\n10 Ten", rule));
+        assert_eq!(menu.choose(&mut vec![], "2", &mut Counter::default(), &mut quiet()).unwrap(),
+                   Chosen::Refused("That option isn't available right now: load first.".to_string()));
+
+        let ansi = MenuStyle { ansi: true, width: 12 };
+        assert_eq!(menu.render_styled(&state, &ansi).lines().take(2).collect::<Vec<_>>(),
+                   ["1 Load", "\x1b[2m2 Countin...\x1b[0m"]);
     }
 }
@@ -0,0 +1,265 @@
+//! a list too long for a menu, shown a page at a time. typed text narrows it down, numbers
+//! pick from it, and with multi-select on, several items can be picked before finishing.
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::Console;
+
+/// items shown on each page unless set otherwise
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+
+/// how to show a list and what may be picked from it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Picker {
+    title: String,
+    page_size: usize,
+    multi: bool,
+}
+
+impl Picker {
+    /// a picker for one item, ten to a page
+    pub fn new(title: &str) -> Picker {
+        Picker {
+            title: String::from(title),
+            page_size: DEFAULT_PAGE_SIZE,
+            multi: false,
+        }
+    }
+
+    /// show this many items on each page
+    pub fn page_size(mut self, page_size: usize) -> Picker {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// let any number of items be picked, finishing with d
+    pub fn multi_select(mut self) -> Picker {
+        self.multi = true;
+        self
+    }
+
+    /// show the list and let one item be picked. returns its index in `items`, or None if cancelled.
+    pub fn pick_one(&self, con: &mut Console, items: &[impl Display]) -> io::Result<Option<usize>> {
+        let single = Picker { multi: false, ..self.clone() };
+        Ok(single.pick(con, items)?.and_then(|picked| picked.first().copied()))
+    }
+
+    /// show the list and let items be picked: one, or with multi-select as many as wanted.
+    /// returns their indexes in `items` in list order, or None if cancelled.
+    /// items are numbered from 1 by their place in the whole list, filtered or not.
+    pub fn pick(&self, con: &mut Console, items: &[impl Display]) -> io::Result<Option<Vec<usize>>> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let texts: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+        let mut browse = Browse {
+            filter: String::new(),
+            shown: (0..texts.len()).collect(),
+            page: 0,
+            picked: BTreeSet::new(),
+        };
+
+        self.show_page(con, &texts, &browse)?;
+        writeln!(con, "{}", self.help())?;
+        loop {
+            if self.multi {
+                write!(con, "Pick items, d when done ({} picked): ", browse.picked.len())?;
+            } else {
+                write!(con, "Pick an item (? for help): ")?;
+            }
+            let Some(line) = con.read_line()? else {
+                return Ok(None);
+            };
+            let answer = line.trim();
+            let pages = browse.shown.len().div_ceil(self.page_size).max(1);
+
+            match answer.to_lowercase().as_str() {
+                "" | "n" if browse.page + 1 < pages => browse.page += 1,
+                "" | "n" => {
+                    writeln!(con, "That's the last page.")?;
+                    continue;
+                }
+                "p" if browse.page > 0 => browse.page -= 1,
+                "p" => {
+                    writeln!(con, "That's the first page.")?;
+                    continue;
+                }
+                "x" => return Ok(None),
+                "?" => {
+                    writeln!(con, "{}", self.help())?;
+                    continue;
+                }
+                "a" if self.multi => browse.picked.extend(&browse.shown),
+                "d" if self.multi && browse.picked.is_empty() => {
+                    writeln!(con, "Nothing is picked yet.")?;
+                    continue;
+                }
+                "d" if self.multi => return Ok(Some(browse.picked.into_iter().collect())),
+                _ if answer.starts_with('/') => {
+                    browse.filter = answer[1..].trim().to_string();
+                    browse.shown = containing(&texts, &browse.filter);
+                    browse.page = 0;
+                }
+                command if command.starts_with('g') => {
+                    let Some(item) = item_number(command[1..].trim(), texts.len()) else {
+                        writeln!(con, "Enter g and a number from 1 to {}.", texts.len())?;
+                        continue;
+                    };
+                    // an item the filter hides can only be shown by dropping the filter
+                    if !browse.shown.contains(&item) {
+                        browse.filter.clear();
+                        browse.shown = (0..texts.len()).collect();
+                    }
+                    let at = browse.shown.iter().position(|&i| i == item).expect("item is shown");
+                    browse.page = at / self.page_size;
+                }
+                command => match item_numbers(command, texts.len()) {
+                    Some(range) if !self.multi && range.start() == range.end() => return Ok(Some(vec![*range.start()])),
+                    Some(range) if self.multi && range.start() == range.end() => {
+                        let item = *range.start();
+                        if !browse.picked.remove(&item) {
+                            browse.picked.insert(item);
+                        }
+                    }
+                    Some(range) if self.multi => browse.picked.extend(range),
+                    _ => {
+                        writeln!(con, "That isn't one of the choices. Type ? for help.")?;
+                        continue;
+                    }
+                },
+            }
+            self.show_page(con, &texts, &browse)?;
+        }
+    }
+
+    fn help(&self) -> &'static str {
+        if self.multi {
+            "Enter a number, or a range like 3-7, to pick or unpick items; a picks everything shown \
+             and d finishes. n and p page through the list, /text shows only items containing text \
+             (/ on its own shows them all), g and a number goes to that item, and x cancels."
+        } else {
+            "Enter a number to pick that item. n and p page through the list, /text shows only items \
+             containing text (/ on its own shows them all), g and a number goes to that item, and x cancels."
+        }
+    }
+
+    fn show_page(&self, con: &mut Console, texts: &[String], browse: &Browse) -> io::Result<()> {
+        let style = con.get_style();
+        writeln!(con)?;
+        if browse.shown.is_empty() {
+            return writeln!(con, "Nothing contains \"{}\".", browse.filter);
+        }
+
+        let start = browse.page * self.page_size;
+        let end = (start + self.page_size).min(browse.shown.len());
+        let filtered = match browse.filter.is_empty() {
+            true => String::new(),
+            false => format!(" containing \"{}\"", browse.filter),
+        };
+        let header = format!("{} {}-{} of {}{}, page {} of {}", self.title, start + 1, end, browse.shown.len(), filtered,
+                             browse.page + 1, browse.shown.len().div_ceil(self.page_size));
+        writeln!(con, "{}", style.bold(&style.fit(header.trim_start())))?;
+
+        let width = texts.len().to_string().len();
+        for &item in &browse.shown[start..end] {
+            let mark = match (self.multi, browse.picked.contains(&item)) {
+                (true, true) => "*",
+                _ => " ",
+            };
+            let line = format!("{} {:>width$}) {}", mark, item + 1, texts[item], width = width);
+            writeln!(con, "{}", style.fit(&line))?;
+        }
+        Ok(())
+    }
+}
+
+/// where a picker is in its list
+struct Browse {
+    filter: String,
+    /// indexes of the items the filter lets through
+    shown: Vec<usize>,
+    page: usize,
+    picked: BTreeSet<usize>,
+}
+
+/// indexes of the texts containing `filter`, ignoring case
+fn containing(texts: &[String], filter: &str) -> Vec<usize> {
+    let filter = filter.to_lowercase();
+    (0..texts.len()).filter(|&i| texts[i].to_lowercase().contains(&filter)).collect()
+}
+
+/// the index of the item numbered `answer`, counting from 1
+fn item_number(answer: &str, count: usize) -> Option<usize> {
+    match answer.parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Some(n - 1),
+        _ => None,
+    }
+}
+
+/// the indexes of the items numbered by `answer`: one number, or two joined by a dash
+fn item_numbers(answer: &str, count: usize) -> Option<RangeInclusive<usize>> {
+    match answer.split_once('-') {
+        Some((from, to)) => {
+            let (from, to) = (item_number(from.trim(), count)?, item_number(to.trim(), count)?);
+            Some(from.min(to)..=from.max(to))
+        }
+        None => item_number(answer, count).map(|n| n..=n),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        (1..=25).map(|n| format!("Customer {}", n)).collect()
+    }
+
+    /// pick from the names with a script, returning the picks and everything shown
+    fn play(picker: &Picker, typed: &str) -> (Option<Vec<usize>>, String) {
+        let mut shown = vec![];
+        let picked = picker.pick(&mut Console::new(typed.as_bytes(), &mut shown), &names()).unwrap();
+        (picked, String::from_utf8(shown).unwrap())
+    }
+
+    #[test]
+    fn pages_filters_and_picks_one() {
+        let picker = Picker::new("Customers").page_size(4);
+        let (picked, shown) = play(&picker, "n\np\np\n/customer 2\n\nwho\n26\n21\n");
+        assert_eq!(picked, Some(vec![20]));
+        assert!(shown.starts_with("\nCustomers 1-4 of 25, page 1 of 7\n   1) Customer 1\n   2) Customer 2\n"));
+        assert!(shown.contains("\nCustomers 5-8 of 25, page 2 of 7\n   5) Customer 5\n"));
+        assert!(shown.contains("That's the first page.\n"));
+        assert!(shown.contains("\nCustomers 1-4 of 7 containing \"customer 2\", page 1 of 2\n   2) Customer 2\n  20) Customer 20\n"));
+        assert!(shown.contains("\nCustomers 5-7 of 7 containing \"customer 2\", page 2 of 2\n  23) Customer 23\n"));
+        assert!(shown.ends_with("Pick an item (? for help): That isn't one of the choices. Type ? for help.\n\
+                                 Pick an item (? for help): That isn't one of the choices. Type ? for help.\n\
+                                 Pick an item (? for help): "));
+
+        // going to an item the filter hides drops the filter
+        let (picked, shown) = play(&picker, "/customer 1\ng 22\nx\n");
+        assert_eq!(picked, None);
+        assert!(shown.contains("\nCustomers 21-24 of 25, page 6 of 7\n  21) Customer 21\n"));
+        assert_eq!(play(&picker, "").0, None);
+    }
+
+    #[test]
+    fn picks_many() {
+        let picker = Picker::new("Customers").page_size(5).multi_select();
+        let (picked, shown) = play(&picker, "d\n3-1\n2\n/Customer 2\na\nd\n");
+        assert_eq!(picked, Some(vec![0, 1, 2, 19, 20, 21, 22, 23, 24]));
+        assert!(shown.contains("Pick items, d when done (0 picked): Nothing is picked yet.\n"));
+        assert!(shown.contains("Pick items, d when done (3 picked): \n\
+                                Customers 1-5 of 25, page 1 of 5\n*  1) Customer 1\n   2) Customer 2\n*  3) Customer 3\n"));
+        assert!(shown.ends_with("Pick items, d when done (9 picked): "));
+
+        // picking one from a multi-select picker
+        let mut shown = vec![];
+        let one = picker.pick_one(&mut Console::new("7\n".as_bytes(), &mut shown), &names()).unwrap();
+        assert_eq!(one, Some(6));
+    }
+}
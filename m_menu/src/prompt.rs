@@ -0,0 +1,193 @@
+//! prompts that ask for a value, check it, and ask again until they get a usable answer.
+//! empty input can cancel the prompt or fall back to a default; end of input always cancels.
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use crate::Console;
+
+/// a question to ask, and how to treat empty answers
+#[derive(Debug, PartialEq, Clone)]
+pub struct Prompt {
+    text: String,
+    default: Option<String>,
+    cancel_on_empty: bool,
+}
+
+impl Prompt {
+    /// a prompt that asks again when nothing is entered
+    pub fn new(text: &str) -> Prompt {
+        Prompt {
+            text: String::from(text),
+            default: None,
+            cancel_on_empty: false,
+        }
+    }
+
+    /// cancel the prompt when nothing is entered, unless there's a default
+    pub fn cancel_on_empty(mut self) -> Prompt {
+        self.cancel_on_empty = true;
+        self
+    }
+
+    /// use this answer when nothing is entered. it's shown in brackets and checked like typed input.
+    pub fn default(mut self, default: impl Display) -> Prompt {
+        self.default = Some(default.to_string());
+        self
+    }
+
+    /// the prompt as shown, with any default in brackets before the trailing colon
+    fn shown(&self) -> String {
+        match &self.default {
+            Some(default) => {
+                let text = self.text.trim_end();
+                match text.strip_suffix(':') {
+                    Some(question) => format!("{} [{}]: ", question, default),
+                    None => format!("{} [{}] ", text, default),
+                }
+            }
+            None => self.text.clone(),
+        }
+    }
+
+    /// ask until `validate` accepts the answer, showing its error message each time it doesn't.
+    /// returns None if cancelled.
+    pub fn ask<T>(&self, con: &mut Console, validate: impl Fn(&str) -> Result<T, String>) -> io::Result<Option<T>> {
+        loop {
+            write!(con, "{}", self.shown())?;
+            let Some(line) = con.read_line()? else {
+                return Ok(None);
+            };
+
+            let answer = line.trim();
+            let answer = match (&self.default, answer.is_empty()) {
+                (Some(default), true) => default.as_str(),
+                (None, true) if self.cancel_on_empty => return Ok(None),
+                (None, true) => {
+                    writeln!(con, "Please enter a value.")?;
+                    continue;
+                }
+                (_, false) => answer,
+            };
+            match validate(answer) {
+                Ok(value) => return Ok(Some(value)),
+                Err(message) => writeln!(con, "{}", message)?,
+            }
+        }
+    }
+
+    /// ask for any text
+    pub fn text(&self, con: &mut Console) -> io::Result<Option<String>> {
+        self.ask(con, |answer| Ok(answer.to_string()))
+    }
+
+    /// ask for a whole number within `range`
+    pub fn number(&self, con: &mut Console, range: RangeInclusive<usize>) -> io::Result<Option<usize>> {
+        self.ask(con, number_in(range))
+    }
+
+    /// ask a yes or no question
+    pub fn yes_no(&self, con: &mut Console) -> io::Result<Option<bool>> {
+        self.ask(con, yes_or_no)
+    }
+
+    /// list the choices numbered from 1 and ask for one. returns its index in `choices`.
+    pub fn choose(&self, con: &mut Console, choices: &[impl Display]) -> io::Result<Option<usize>> {
+        if choices.is_empty() {
+            return Ok(None);
+        }
+        let width = choices.len().to_string().len();
+        for (n, choice) in choices.iter().enumerate() {
+            writeln!(con, "  {:>width$}) {}", n + 1, choice, width = width)?;
+        }
+        Ok(self.ask(con, number_in(1..=choices.len()))?.map(|n| n - 1))
+    }
+
+    /// ask for anything that can be made from a string, showing the conversion error when it can't
+    pub fn parse<T>(&self, con: &mut Console) -> io::Result<Option<T>> where T: TryFrom<String>, T::Error: Display {
+        self.ask(con, parsed::<T>)
+    }
+}
+
+/// ask for anything that can be made from a string; cancels on empty input
+pub fn prompt_parse<T>(con: &mut Console, text: &str) -> io::Result<Option<T>> where T: TryFrom<String>, T::Error: Display {
+    Prompt::new(text).cancel_on_empty().parse(con)
+}
+
+fn number_in(range: RangeInclusive<usize>) -> impl Fn(&str) -> Result<usize, String> {
+    move |answer| match answer.parse::<usize>() {
+        Ok(n) if range.contains(&n) => Ok(n),
+        _ => Err(format!("Enter a number from {} to {}.", range.start(), range.end())),
+    }
+}
+
+fn yes_or_no(answer: &str) -> Result<bool, String> {
+    match answer.to_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        _ => Err("Enter y or n.".to_string()),
+    }
+}
+
+fn parsed<T>(answer: &str) -> Result<T, String> where T: TryFrom<String>, T::Error: Display {
+    T::try_from(answer.to_string()).map_err(|e| e.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// answer a prompt from a script, returning the value and everything shown
+    fn answer<T>(prompt: &Prompt, typed: &str, validate: impl Fn(&str) -> Result<T, String>) -> (Option<T>, String) {
+        let mut shown = vec![];
+        let value = prompt.ask(&mut Console::new(typed.as_bytes(), &mut shown), validate).unwrap();
+        (value, String::from_utf8(shown).unwrap())
+    }
+
+    #[test]
+    fn retries_with_the_error() {
+        let prompt = Prompt::new("Count: ");
+        let (value, shown) = answer(&prompt, "\nlots\n12\n4\n", number_in(1..=5));
+        assert_eq!(value, Some(4));
+        assert_eq!(shown, "Count: Please enter a value.\nCount: Enter a number from 1 to 5.\n\
+                           Count: Enter a number from 1 to 5.\nCount: ");
+
+        assert_eq!(answer(&prompt, "maybe\nYES\n", yes_or_no).0, Some(true));
+        assert_eq!(answer(&prompt, "x\n", yes_or_no).0, None);
+    }
+
+    #[test]
+    fn cancels_and_defaults() {
+        let cancelling = Prompt::new("Name: ").cancel_on_empty();
+        assert_eq!(answer(&cancelling, "\nJo\n", |a| Ok(a.to_string())).0, None);
+
+        let defaulted = Prompt::new("Copies: ").cancel_on_empty().default(2);
+        let (value, shown) = answer(&defaulted, "\n", number_in(1..=9));
+        assert_eq!((value, shown.as_str()), (Some(2), "Copies [2]: "));
+        assert_eq!(answer(&defaulted, " 7 \n", number_in(1..=9)).0, Some(7));
+    }
+
+    #[test]
+    fn choices_and_parsing() {
+        let mut shown = vec![];
+        let picked = Prompt::new("Pick: ").choose(&mut Console::new("3\n2\n".as_bytes(), &mut shown), &["red", "blue"]).unwrap();
+        assert_eq!(picked, Some(1));
+        assert!(String::from_utf8(shown).unwrap().starts_with("  1) red\n  2) blue\nPick: Enter a number from 1 to 2."));
+
+        #[derive(Debug, PartialEq)]
+        struct Even(u32);
+        impl TryFrom<String> for Even {
+            type Error = String;
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                match s.parse::<u32>() {
+                    Ok(n) if n.is_multiple_of(2) => Ok(Even(n)),
+                    _ => Err(format!("{} isn't even", s)),
+                }
+            }
+        }
+        let (value, shown) = answer(&Prompt::new("> "), "3\n8\n", parsed::<Even>);
+        assert_eq!((value, shown.as_str()), (Some(Even(8)), "> 3 isn't even\n> "));
+    }
+}
@@ -0,0 +1,87 @@
+//! how menus are drawn: plain text, or with ANSI bold and dim when writing to a terminal,
+//! fitted to the terminal's width.
+use std::io::IsTerminal;
+
+/// width assumed when the terminal doesn't say
+pub const DEFAULT_WIDTH: usize = 80;
+/// narrowest width menus are squeezed into
+const MIN_WIDTH: usize = 20;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MenuStyle {
+    /// use ANSI escape codes for bold and dim text
+    pub ansi: bool,
+    /// longest line to draw, in characters
+    pub width: usize,
+}
+
+impl Default for MenuStyle {
+    fn default() -> Self {
+        MenuStyle::plain()
+    }
+}
+
+impl MenuStyle {
+    /// no escape codes, and no line is cut short; for output that isn't a terminal
+    pub fn plain() -> MenuStyle {
+        MenuStyle { ansi: false, width: usize::MAX }
+    }
+
+    /// style for stdout: plain if it isn't a terminal. otherwise ANSI (unless NO_COLOR is set
+    /// or TERM is dumb), as wide as $COLUMNS says
+    pub fn detect() -> MenuStyle {
+        if !std::io::stdout().is_terminal() {
+            return MenuStyle::plain();
+        }
+        let ansi = std::env::var_os("NO_COLOR").is_none() && std::env::var("TERM").map_or(true, |t| t != "dumb");
+        let width = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(DEFAULT_WIDTH);
+        MenuStyle { ansi, width: width.max(MIN_WIDTH) }
+    }
+
+    /// cut text that won't fit in the width, marking the cut with ...
+    pub fn fit(&self, text: &str) -> String {
+        if text.chars().count() <= self.width {
+            return text.to_string();
+        }
+        let kept: String = text.chars().take(self.width.saturating_sub(3)).collect();
+        format!("{}...", kept)
+    }
+
+    pub fn bold(&self, text: &str) -> String {
+        self.wrap(BOLD, text)
+    }
+
+    pub fn dim(&self, text: &str) -> String {
+        self.wrap(DIM, text)
+    }
+
+    fn wrap(&self, code: &str, text: &str) -> String {
+        if self.ansi {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_and_styles() {
+        let narrow = MenuStyle { ansi: false, width: 10 };
+        assert_eq!(narrow.fit("1 Short"), "1 Short");
+        assert_eq!(narrow.fit("1 Much too long"), "1 Much ...");
+        assert_eq!(narrow.bold("x"), "x");
+
+        let ansi = MenuStyle { ansi: true, width: 10 };
+        assert_eq!(ansi.bold("x"), "\x1b[1mx\x1b[0m");
+        assert_eq!(ansi.dim("x"), "\x1b[2mx\x1b[0m");
+    }
+}